@@ -0,0 +1,307 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::auth::async_token_validator::SyncOnlyTokenValidator;
+use crate::auth::claims_token::ClaimsToken;
+use crate::auth::error_kind::INVALID_TOKEN;
+use crate::auth::token::{Token, SCOPE_CLAIM, SUBJECT_CLAIM};
+use crate::auth::token_validator::TokenValidator;
+use crate::config_reader::ConfigReader;
+use crate::error::Error;
+use crate::error_kind::SERIALIZATION_FAILURE;
+use crate::secrets::secrets_manager::SecretsManager;
+
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+
+const SHA256_LENGTH: usize = 32;
+
+/// `ApiKey` describes a static API key by the SHA-256 hash of its value, so the keys
+/// themselves never need to be stored.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ApiKey {
+    #[serde(rename = "Owner")]
+    owner: String,
+    /// Hex encoded SHA-256 hash of the key, i.e. the output of `sha256sum`.
+    #[serde(rename = "Hash")]
+    hash: String,
+    #[serde(rename = "Scopes", default)]
+    scopes: Vec<String>,
+}
+
+impl ApiKey {
+    pub fn new(owner: impl Into<String>, hash: impl Into<String>, scopes: Vec<String>) -> Self {
+        Self {
+            owner: owner.into(),
+            hash: hash.into(),
+            scopes,
+        }
+    }
+
+    /// Creates an `ApiKey` from the plain key, which is hashed right away.
+    pub fn from_key(owner: impl Into<String>, key: &str, scopes: Vec<String>) -> Self {
+        Self::new(
+            owner,
+            hex(Sha256::digest(key.as_bytes()).as_slice()),
+            scopes,
+        )
+    }
+
+    ///
+    /// Creates an `ApiKey` from a plain key stored within the secrets manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets_manager` - Secrets manager holding the key.
+    /// * `secret_id` - ID of the key within the secrets manager.
+    /// * `owner` - Owner of the key, used as the token's subject.
+    /// * `scopes` - Scopes granted to the key.
+    pub fn from_secrets_manager(
+        secrets_manager: &dyn SecretsManager,
+        secret_id: &str,
+        owner: impl Into<String>,
+        scopes: Vec<String>,
+    ) -> Result<Self, Error> {
+        let key = secrets_manager.get_secret(secret_id)?;
+
+        Ok(Self::from_key(owner, key.as_str(), scopes))
+    }
+
+    pub fn owner(&self) -> &str {
+        self.owner.as_str()
+    }
+
+    pub fn scopes(&self) -> &[String] {
+        self.scopes.as_slice()
+    }
+}
+
+#[derive(Deserialize)]
+struct ApiKeyList {
+    #[serde(rename = "ApiKeys", default)]
+    api_keys: Vec<ApiKey>,
+}
+
+/// `ApiKeyValidator` validates static API keys. The presented key is hashed and compared in
+/// constant time against every known hash, yielding a token with the key's owner as `sub` and
+/// its scopes as `scope`.
+pub struct ApiKeyValidator {
+    api_keys: Vec<(ApiKey, [u8; SHA256_LENGTH])>,
+}
+
+impl ApiKeyValidator {
+    ///
+    /// # Arguments
+    ///
+    /// * `api_keys` - Accepted API keys.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validator accepting `api_keys`.
+    /// * `Err` - Error with the kind `SERIALIZATION_FAILURE` if a hash is not a hex encoded
+    ///   SHA-256 hash.
+    pub fn new(api_keys: Vec<ApiKey>) -> Result<Self, Error> {
+        let mut hashed_api_keys = Vec::with_capacity(api_keys.len());
+
+        for api_key in api_keys {
+            let hash = match parse_hash(api_key.hash.as_str()) {
+                Some(hash) => hash,
+                None => {
+                    return Err(Error::new(
+                        SERIALIZATION_FAILURE,
+                        format!(
+                            "hash of the API key of '{}' is not a hex encoded SHA-256 hash",
+                            api_key.owner
+                        ),
+                    ))
+                }
+            };
+
+            hashed_api_keys.push((api_key, hash));
+        }
+
+        Ok(Self {
+            api_keys: hashed_api_keys,
+        })
+    }
+
+    ///
+    /// Loads the API keys from the specified YAML file:
+    ///
+    /// ```yaml
+    /// ApiKeys:
+    ///   - Owner: "billing-service"
+    ///     Hash: "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+    ///     Scopes:
+    ///       - "invoices:read"
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path of the YAML file containing the API keys.
+    pub fn from_config(file_path: PathBuf) -> Result<Self, Error> {
+        let value = ConfigReader::default().read(file_path)?;
+        let api_key_list = serde_yaml::from_value::<ApiKeyList>(value)?;
+
+        Self::new(api_key_list.api_keys)
+    }
+
+    fn find(&self, key: &str) -> Option<&ApiKey> {
+        let hash = Sha256::digest(key.as_bytes());
+        let mut found: Option<&ApiKey> = None;
+
+        // every hash is compared so the timing does not reveal which key matched
+        for (api_key, expected_hash) in self.api_keys.iter() {
+            let matches: Choice = expected_hash.as_slice().ct_eq(hash.as_slice());
+
+            if bool::from(matches) {
+                found = Some(api_key);
+            }
+        }
+
+        found
+    }
+}
+
+impl SyncOnlyTokenValidator for ApiKeyValidator {}
+
+impl TokenValidator for ApiKeyValidator {
+    fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let api_key = match self.find(token) {
+            Some(api_key) => api_key,
+            None => return Err(Error::new(INVALID_TOKEN, "API key is not valid")),
+        };
+
+        let mut claims: HashMap<String, Value> = HashMap::new();
+        claims.insert(SUBJECT_CLAIM.to_string(), json!(api_key.owner));
+        claims.insert(SCOPE_CLAIM.to_string(), json!(api_key.scopes.join(" ")));
+
+        Ok(Arc::new(ClaimsToken::new(claims)))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hash(hash: &str) -> Option<[u8; SHA256_LENGTH]> {
+    if hash.len() != SHA256_LENGTH * 2 || !hash.is_ascii() {
+        return None;
+    }
+
+    let mut parsed_hash = [0u8; SHA256_LENGTH];
+
+    for (index, byte) in parsed_hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hash[index * 2..index * 2 + 2], 16).ok()?;
+    }
+
+    Some(parsed_hash)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::auth::api_key_validator::{ApiKey, ApiKeyValidator};
+    use crate::auth::error_kind::INVALID_TOKEN;
+    use crate::auth::token_validator::TokenValidator;
+    use crate::error::Error;
+    use crate::error_kind::SERIALIZATION_FAILURE;
+    use crate::secrets::secrets_manager::SecretsManager;
+    use crate::test_base::get_unit_test_data_path;
+
+    #[test]
+    pub fn validate_known_key_returns_owner_and_scopes() {
+        let api_key_validator = ApiKeyValidator::new(vec![
+            ApiKey::from_key(
+                "billing-service",
+                "billing-key",
+                vec!["invoices:read".into()],
+            ),
+            ApiKey::from_key("report-service", "report-key", vec![]),
+        ])
+        .expect("expected API key validator");
+
+        let token = api_key_validator
+            .validate("billing-key")
+            .expect("expected valid API key");
+
+        assert_eq!(Some("billing-service".to_string()), token.subject());
+        assert_eq!(vec!["invoices:read"], token.scopes());
+    }
+
+    #[test]
+    pub fn validate_unknown_key_returns_invalid_token() {
+        let api_key_validator = ApiKeyValidator::new(vec![ApiKey::from_key(
+            "billing-service",
+            "billing-key",
+            vec![],
+        )])
+        .expect("expected API key validator");
+
+        let error = api_key_validator
+            .validate("other-key")
+            .expect_err("expected invalid API key");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn new_with_invalid_hash_returns_error() {
+        for hash in [
+            "abc",
+            "zz86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08",
+        ] {
+            let error = ApiKeyValidator::new(vec![ApiKey::new("owner", hash, vec![])])
+                .err()
+                .expect("expected invalid hash");
+
+            assert_eq!(SERIALIZATION_FAILURE, error.error_kind());
+        }
+    }
+
+    #[test]
+    pub fn from_config_loads_hashed_keys() {
+        let mut path = get_unit_test_data_path(file!());
+        path.push("api_keys.yaml");
+        let api_key_validator = ApiKeyValidator::from_config(path).expect("expected API keys");
+
+        // sha256 of 'test'
+        let token = api_key_validator
+            .validate("test")
+            .expect("expected valid API key");
+
+        assert_eq!(Some("billing-service".to_string()), token.subject());
+        assert_eq!(vec!["invoices:read", "invoices:write"], token.scopes());
+    }
+
+    #[test]
+    pub fn from_secrets_manager_hashes_secret() {
+        let api_key = ApiKey::from_secrets_manager(
+            &StaticSecretsManager,
+            "billing-key-id",
+            "billing-service",
+            vec![],
+        )
+        .expect("expected API key");
+        let api_key_validator =
+            ApiKeyValidator::new(vec![api_key]).expect("expected API key validator");
+
+        assert!(api_key_validator.validate("billing-key").is_ok());
+    }
+
+    struct StaticSecretsManager;
+
+    impl SecretsManager for StaticSecretsManager {
+        fn get_secret(&self, _: &str) -> Result<String, Error> {
+            Ok("billing-key".to_string())
+        }
+    }
+}
@@ -0,0 +1,54 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+#[cfg(test)]
+use mockall::automock;
+
+use crate::auth::token::Token;
+use crate::auth::token_validator::TokenValidator;
+use crate::error::Error;
+
+/// `AsyncTokenValidator` validates tokens which may require I/O, i.e. reaching an
+/// introspection endpoint. Every `SyncOnlyTokenValidator` is also an `AsyncTokenValidator`.
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait AsyncTokenValidator {
+    ///
+    /// Validates the specified token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Token to be validated.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Token.
+    /// * `Err` - Error if the validation has failed.
+    ///   Having the error kind valued as `INVALID_TOKEN` if the token is invalid.
+    async fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error>;
+}
+
+/// `SyncOnlyTokenValidator` marks the `TokenValidator`s which never wait for I/O, so they are
+/// validated as `AsyncTokenValidator`s as is. Validators which may wait, i.e. for refreshing
+/// their keys, implement `AsyncTokenValidator` directly instead.
+pub trait SyncOnlyTokenValidator: TokenValidator {}
+
+impl<T: SyncOnlyTokenValidator + ?Sized> SyncOnlyTokenValidator for Arc<T> {}
+
+// a 'TokenValidator' trait object can only be validated through its synchronous 'validate'
+impl SyncOnlyTokenValidator for dyn TokenValidator + Send + Sync {}
+
+#[cfg(test)]
+impl SyncOnlyTokenValidator for crate::auth::token_validator::MockTokenValidator {}
+
+#[async_trait]
+impl<T: SyncOnlyTokenValidator + Send + Sync + ?Sized> AsyncTokenValidator for T {
+    async fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        TokenValidator::validate(self, token)
+    }
+}
@@ -0,0 +1,344 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use axum::extract::ConnectInfo;
+use axum::http::request::Parts;
+use jsonwebtoken::decode_header;
+#[cfg(test)]
+use mockall::automock;
+
+use crate::auth::credentials::Credentials;
+use crate::auth::jwt_token::unverified_issuer;
+use crate::auth::token::Token;
+use crate::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+impl Display for AuditOutcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Success => f.write_str("success"),
+            Self::Failure => f.write_str("failure"),
+        }
+    }
+}
+
+/// `AuditEvent` describes the outcome of authenticating a request. It never contains the
+/// credentials themselves.
+///
+/// The issuer of failed requests is read from the token without verifying it, so it must only
+/// be used for telling identity providers apart, i.e. when alerting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditEvent {
+    outcome: AuditOutcome,
+    error_kind: Option<String>,
+    scheme: Option<String>,
+    issuer: Option<String>,
+    subject: Option<String>,
+    key_id: Option<String>,
+    validated_by: Option<String>,
+    client_ip: Option<IpAddr>,
+}
+
+impl AuditEvent {
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - Head of the authenticated request.
+    /// * `credentials` - Credentials found within the request, if any.
+    /// * `token` - Validated token, if the credentials were valid.
+    /// * `error` - Error which made the authentication or the authorization fail, if any.
+    pub fn new(
+        parts: &Parts,
+        credentials: Option<&Credentials>,
+        token: Option<&(dyn Token + Send + Sync)>,
+        error: Option<&Error>,
+    ) -> Self {
+        let raw_token = match credentials {
+            Some(Credentials::Bearer(token)) | Some(Credentials::DPoP(token)) => {
+                Some(token.as_str())
+            }
+            _ => None,
+        };
+
+        Self {
+            outcome: match error {
+                Some(_) => AuditOutcome::Failure,
+                None => AuditOutcome::Success,
+            },
+            error_kind: error.map(|error| error.error_kind().to_string()),
+            scheme: credentials.map(|credentials| credentials.scheme().to_string()),
+            issuer: match token {
+                Some(token) => token.issuer(),
+                None => raw_token.and_then(unverified_issuer),
+            },
+            subject: token.and_then(|token| token.subject()),
+            key_id: raw_token
+                .and_then(|raw_token| decode_header(raw_token).ok())
+                .and_then(|header| header.kid),
+            validated_by: token.and_then(|token| token.validated_by()),
+            client_ip: parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| address.ip()),
+        }
+    }
+
+    pub fn outcome(&self) -> AuditOutcome {
+        self.outcome
+    }
+
+    /// Kind of the error which made the request fail, i.e. `invalid_token`.
+    pub fn error_kind(&self) -> Option<&str> {
+        self.error_kind.as_deref()
+    }
+
+    pub fn scheme(&self) -> Option<&str> {
+        self.scheme.as_deref()
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.subject.as_deref()
+    }
+
+    /// `kid` header of the JWT.
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// Name of the validator which accepted the token, see `CompositeTokenValidator`.
+    pub fn validated_by(&self) -> Option<&str> {
+        self.validated_by.as_deref()
+    }
+
+    /// Address of the client, if the server was started with `ConnectInfo<SocketAddr>`.
+    pub fn client_ip(&self) -> Option<IpAddr> {
+        self.client_ip
+    }
+}
+
+impl Display for AuditEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "outcome={}", self.outcome)?;
+
+        let fields = [
+            ("error_kind", self.error_kind.clone()),
+            ("scheme", self.scheme.clone()),
+            ("issuer", self.issuer.clone()),
+            ("subject", self.subject.clone()),
+            ("kid", self.key_id.clone()),
+            ("validated_by", self.validated_by.clone()),
+            (
+                "client_ip",
+                self.client_ip.map(|client_ip| client_ip.to_string()),
+            ),
+        ];
+
+        for (name, value) in fields {
+            if let Some(value) = value {
+                write!(f, " {}={:?}", name, value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// `AuditSink` receives the audit event of every request authenticated by `Authorization`.
+#[cfg_attr(test, automock)]
+pub trait AuditSink {
+    /// Records the event. It is called on the request path, so it must not block.
+    fn record(&self, event: &AuditEvent);
+}
+
+/// `LogAuditSink` logs successful authentications at info level and failed ones at warn level.
+#[derive(Debug, Default, Clone)]
+pub struct LogAuditSink;
+
+impl AuditSink for LogAuditSink {
+    fn record(&self, event: &AuditEvent) {
+        match event.outcome() {
+            AuditOutcome::Success => log::info!("authentication {}", event),
+            AuditOutcome::Failure => log::warn!("authentication {}", event),
+        }
+    }
+}
+
+/// `AuditCounters` counts the successful authentications and the failed ones by error kind,
+/// i.e. for alerting on spikes of invalid tokens.
+#[derive(Debug, Default)]
+pub struct AuditCounters {
+    successes: AtomicU64,
+    failures: Mutex<HashMap<String, u64>>,
+}
+
+impl AuditCounters {
+    pub fn successes(&self) -> u64 {
+        self.successes.load(Ordering::Relaxed)
+    }
+
+    ///
+    /// # Arguments
+    ///
+    /// * `error_kind` - Kind of the error, i.e. `invalid_token`.
+    ///
+    /// # Returns
+    ///
+    /// Number of the requests which failed with `error_kind`.
+    pub fn failures(&self, error_kind: &str) -> u64 {
+        match self.failures.lock() {
+            Ok(failures) => failures.get(error_kind).copied().unwrap_or_default(),
+            Err(_) => 0,
+        }
+    }
+
+    /// Number of failed requests by error kind.
+    pub fn failures_by_kind(&self) -> HashMap<String, u64> {
+        match self.failures.lock() {
+            Ok(failures) => failures.clone(),
+            Err(_) => HashMap::new(),
+        }
+    }
+}
+
+impl AuditSink for AuditCounters {
+    fn record(&self, event: &AuditEvent) {
+        let error_kind = match event.error_kind() {
+            Some(error_kind) => error_kind,
+            None => {
+                self.successes.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        if let Ok(mut failures) = self.failures.lock() {
+            *failures.entry(error_kind.to_string()).or_default() += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+    use axum::extract::{ConnectInfo, Request};
+    use axum::http::request::Parts;
+    use jsonwebtoken::Algorithm;
+
+    use crate::auth::audit::{AuditCounters, AuditEvent, AuditOutcome, AuditSink};
+    use crate::auth::claims_token::ClaimsToken;
+    use crate::auth::credentials::Credentials;
+    use crate::auth::error_kind::{INVALID_TOKEN, MISSING_CREDENTIALS};
+    use crate::auth::jwt_issuer::{JwtIssuer, SigningKey};
+    use crate::auth::token::MockToken;
+    use crate::error::Error;
+
+    #[test]
+    pub fn new_failure_reads_unverified_issuer_and_key_id() {
+        let issuer = JwtIssuer::new("https://issuer.example/").with_signing_key(
+            SigningKey::generate("key-1", Algorithm::ES256).expect("expected signing key"),
+        );
+        let token = issuer
+            .token()
+            .subject("user-1")
+            .sign()
+            .expect("expected signed token");
+        let error = Error::new(INVALID_TOKEN, "token is not valid");
+
+        let event = AuditEvent::new(
+            &get_parts(),
+            Some(&Credentials::Bearer(token)),
+            None,
+            Some(&error),
+        );
+
+        assert_eq!(AuditOutcome::Failure, event.outcome());
+        assert_eq!(Some(INVALID_TOKEN), event.error_kind());
+        assert_eq!(Some("Bearer"), event.scheme());
+        assert_eq!(Some("https://issuer.example/"), event.issuer());
+        assert_eq!(None, event.subject());
+        assert_eq!(Some("key-1"), event.key_id());
+        assert_eq!(Some(IpAddr::V4(Ipv4Addr::LOCALHOST)), event.client_ip());
+    }
+
+    #[test]
+    pub fn new_success_reads_validated_token() {
+        let mut token = MockToken::new();
+        token
+            .expect_issuer()
+            .returning(|| Some("https://issuer.example/".to_string()));
+        token
+            .expect_subject()
+            .returning(|| Some("user-1".to_string()));
+        token
+            .expect_validated_by()
+            .returning(|| Some("first".to_string()));
+
+        let event = AuditEvent::new(
+            &get_parts(),
+            Some(&Credentials::ApiKey("secret".to_string())),
+            Some(&token),
+            None,
+        );
+
+        assert_eq!(AuditOutcome::Success, event.outcome());
+        assert_eq!(Some("user-1"), event.subject());
+        assert_eq!(Some("first"), event.validated_by());
+        assert_eq!(None, event.key_id());
+        assert!(!format!("{}", event).contains("secret"));
+    }
+
+    #[test]
+    pub fn counters_count_failures_by_error_kind() {
+        let counters = AuditCounters::default();
+        let parts = get_parts();
+        let invalid_token = Error::new(INVALID_TOKEN, "token is not valid");
+        let missing_credentials = Error::new(MISSING_CREDENTIALS, "no token");
+
+        counters.record(&AuditEvent::new(&parts, None, None, Some(&invalid_token)));
+        counters.record(&AuditEvent::new(&parts, None, None, Some(&invalid_token)));
+        counters.record(&AuditEvent::new(
+            &parts,
+            None,
+            None,
+            Some(&missing_credentials),
+        ));
+        counters.record(&AuditEvent::new(
+            &parts,
+            None,
+            Some(&ClaimsToken::new(HashMap::new())),
+            None,
+        ));
+
+        assert_eq!(1, counters.successes());
+        assert_eq!(2, counters.failures(INVALID_TOKEN));
+        assert_eq!(1, counters.failures(MISSING_CREDENTIALS));
+        assert_eq!(2, counters.failures_by_kind().len());
+    }
+
+    fn get_parts() -> Parts {
+        let (mut parts, _) = Request::new(()).into_parts();
+        parts.extensions.insert(ConnectInfo(SocketAddr::new(
+            IpAddr::V4(Ipv4Addr::LOCALHOST),
+            8080,
+        )));
+
+        parts
+    }
+}
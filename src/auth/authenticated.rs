@@ -0,0 +1,48 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use axum::async_trait;
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::response::Response;
+
+use crate::auth::authorization::error_response;
+use crate::auth::error_kind::MISSING_AUTHENTICATION_LAYER;
+use crate::auth::token::Token;
+use crate::error::Error;
+
+/// `Authenticated` is an axum extractor which provides the token validated by the
+/// `AuthenticationLayer` for the current request.
+///
+/// If the request did not go through the `AuthenticationLayer` the extraction is rejected
+/// with a `500 Internal Server Error` response, as the route is misconfigured.
+#[derive(Clone, Debug)]
+pub struct Authenticated(pub Arc<dyn Token + Send + Sync>);
+
+impl Deref for Authenticated {
+    type Target = dyn Token + Send + Sync;
+
+    fn deref(&self) -> &Self::Target {
+        self.0.as_ref()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Authenticated {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        match parts.extensions.get::<Authenticated>() {
+            Some(authenticated) => Ok(authenticated.clone()),
+            None => Err(error_response(&Error::new(
+                MISSING_AUTHENTICATION_LAYER,
+                "request has not gone through the 'AuthenticationLayer', which must be installed \
+                 on the routes using the 'Authenticated' extractor",
+            ))),
+        }
+    }
+}
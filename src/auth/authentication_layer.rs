@@ -0,0 +1,229 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use axum::extract::Request;
+use axum::response::Response;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::auth::authenticated::Authenticated;
+use crate::auth::authorization::{error_response, Authorization};
+use crate::auth::policy::Policy;
+
+/// `AuthenticationLayer` validates the bearer token of every request by using `Authorization`
+/// and, if valid, inserts it into the request extensions as `Authenticated`.
+///
+/// Requests whose token is not valid, or does not fulfill the optional `Policy`, are answered
+/// without reaching the inner service.
+#[derive(Clone)]
+pub struct AuthenticationLayer {
+    authorization: Authorization,
+    policy: Option<Policy>,
+}
+
+impl AuthenticationLayer {
+    pub fn new(authorization: Authorization) -> Self {
+        Self {
+            authorization,
+            policy: None,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: Policy) -> Self {
+        self.policy = Some(policy);
+        self
+    }
+}
+
+impl<S> Layer<S> for AuthenticationLayer {
+    type Service = AuthenticationService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthenticationService {
+            inner,
+            authorization: self.authorization.clone(),
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AuthenticationService<S> {
+    inner: S,
+    authorization: Authorization,
+    policy: Option<Policy>,
+}
+
+impl<S> Service<Request> for AuthenticationService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let authorization = self.authorization.clone();
+        let policy = self.policy.clone();
+        // the service that was polled ready must be the one handling the request
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let (mut parts, body) = request.into_parts();
+            let result = match policy {
+                Some(ref policy) => authorization.authorize_parts(&parts, policy).await,
+                None => authorization.validate_parts(&parts).await,
+            };
+
+            match result {
+                Ok(token) => {
+                    parts.extensions.insert(Authenticated(token));
+                    inner.call(Request::from_parts(parts, body)).await
+                }
+                Err(error) => Ok(error_response(&error)),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use axum::body::Body;
+    use axum::http::header::WWW_AUTHENTICATE;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    use crate::auth::authenticated::Authenticated;
+    use crate::auth::authentication_layer::AuthenticationLayer;
+    use crate::auth::authorization::Authorization;
+    use crate::auth::error_kind::INVALID_TOKEN;
+    use crate::auth::policy::Policy;
+    use crate::auth::token::MockToken;
+    use crate::auth::token_validator::MockTokenValidator;
+    use crate::error::Error;
+
+    #[tokio::test]
+    pub async fn valid_token_reaches_handler() {
+        let router = get_router(AuthenticationLayer::new(get_authorization()));
+
+        let response = router
+            .oneshot(get_request(Some("Bearer valid")))
+            .await
+            .expect("expected response");
+
+        assert_eq!(StatusCode::OK, response.status());
+    }
+
+    #[tokio::test]
+    pub async fn invalid_token_returns_unauthorized_with_challenge() {
+        let router = get_router(AuthenticationLayer::new(get_authorization()));
+
+        let response = router
+            .oneshot(get_request(Some("Bearer invalid")))
+            .await
+            .expect("expected response");
+
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+        let challenge = response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .expect("expected 'WWW-Authenticate' header")
+            .to_str()
+            .expect("expected 'WWW-Authenticate' as string");
+        assert!(challenge.starts_with("Bearer error=\"invalid_token\""));
+    }
+
+    #[tokio::test]
+    pub async fn missing_header_returns_unauthorized() {
+        let router = get_router(AuthenticationLayer::new(get_authorization()));
+
+        let response = router
+            .oneshot(get_request(None))
+            .await
+            .expect("expected response");
+
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+    }
+
+    #[tokio::test]
+    pub async fn unfulfilled_policy_returns_forbidden() {
+        let layer =
+            AuthenticationLayer::new(get_authorization()).with_policy(Policy::scope("admin"));
+        let router = get_router(layer);
+
+        let response = router
+            .oneshot(get_request(Some("Bearer valid")))
+            .await
+            .expect("expected response");
+
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+    }
+
+    #[tokio::test]
+    pub async fn extractor_without_layer_returns_internal_server_error() {
+        let router: Router = Router::new().route("/", get(handler));
+
+        let response = router
+            .oneshot(get_request(Some("Bearer valid")))
+            .await
+            .expect("expected response");
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert!(response.headers().get(WWW_AUTHENTICATE).is_none());
+    }
+
+    async fn handler(authenticated: Authenticated) -> String {
+        authenticated.subject().unwrap_or_default()
+    }
+
+    fn get_router(layer: AuthenticationLayer) -> Router {
+        Router::new().route("/", get(handler)).layer(layer)
+    }
+
+    fn get_request(authorization: Option<&str>) -> Request<Body> {
+        let mut builder = Request::builder().uri("/");
+
+        if let Some(authorization) = authorization {
+            builder = builder.header("Authorization", authorization);
+        }
+
+        builder.body(Body::empty()).expect("expected request")
+    }
+
+    fn get_authorization() -> Authorization {
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock
+            .expect_validate()
+            .returning(|token| match token {
+                "valid" => {
+                    let mut token_mock = MockToken::new();
+                    token_mock
+                        .expect_subject()
+                        .returning(|| Some("user-1".to_string()));
+                    token_mock
+                        .expect_scopes()
+                        .returning(|| vec!["read".to_string()]);
+
+                    Ok(Arc::new(token_mock))
+                }
+                _ => Err(Error::new(INVALID_TOKEN, "token is not valid")),
+            });
+
+        Authorization::new(Arc::new(token_validator_mock))
+    }
+}
@@ -4,39 +4,217 @@
 
 use std::sync::Arc;
 
-use axum::http::{HeaderMap, StatusCode};
+use axum::extract::{Query, Request};
+use axum::http::header::{COOKIE, WWW_AUTHENTICATE};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderValue, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
 
-use crate::auth::error_kind::{INVALID_HEADERS, INVALID_TOKEN};
+use crate::auth::async_token_validator::AsyncTokenValidator;
+use crate::auth::audit::{AuditEvent, AuditSink};
+use crate::auth::client_certificate::{
+    verify_certificate_binding, ClientCertificate, ClientCertificateMode,
+};
+use crate::auth::credentials::Credentials;
+use crate::auth::dpop::{jwk_thumbprint_confirmation, verify_binding, DpopValidator};
+use crate::auth::error_kind::{
+    INVALID_TOKEN, MALFORMED_CREDENTIALS, MISSING_CREDENTIALS, MULTIPLE_CREDENTIALS, REVOKED_TOKEN,
+    UNSUPPORTED_AUTHORIZATION_SCHEME,
+};
+use crate::auth::policy::Policy;
+use crate::auth::revocation_store::RevocationStore;
+use crate::auth::token::Token;
 use crate::auth::token_validator::TokenValidator;
 use crate::error::Error;
+use crate::error_kind::ErrorKind;
+use crate::error_response;
 use crate::{ok_or_return_error, some_or_return_error};
 
 pub const AUTHORIZATION_HEADER: &str = "Authorization";
 
+///
+/// Authorizes the request within a handler, returning early with the response built by
+/// `error_response` if it fails, so handlers using it return `Result<_, Response>`. Handlers
+/// which still need the former `(StatusCode, String)` rejection can build it from the error of
+/// `Authorization::validate` or `Authorization::authorize` with `status_code`.
+///
+/// # Arguments
+///
+/// * `authorization` - `Authorization` validating the request.
+/// * `headers` - `HeaderMap` of the request.
+/// * `policy` - Optional `Policy` the token must satisfy.
 #[macro_export]
 macro_rules! authorize {
     ($authorization: expr, $headers: expr) => {
         match $authorization.validate($headers.clone()).await {
-            Ok(_) => (),
-            Err(error) => {
-                return if error.error_kind() == INVALID_TOKEN {
-                    Err((StatusCode::UNAUTHORIZED, format!("{}", error)))
-                } else {
-                    Err((StatusCode::BAD_REQUEST, format!("{}", error)))
-                }
-            }
+            Ok(token) => token,
+            Err(error) => return Err($crate::auth::authorization::error_response(&error)),
+        }
+    };
+    ($authorization: expr, $headers: expr, $policy: expr) => {
+        match $authorization.authorize($headers.clone(), &$policy).await {
+            Ok(token) => token,
+            Err(error) => return Err($crate::auth::authorization::error_response(&error)),
         }
     };
 }
 
+///
+/// Maps an authorization error to the HTTP status code to be returned to the client, as per
+/// the installed `StatusCodeTable`. By default:
+///
+/// * `MISSING_CREDENTIALS`, `INVALID_TOKEN`, `INVALID_DPOP_PROOF`, `REVOKED_TOKEN` -
+///   `401 Unauthorized`.
+/// * `FORBIDDEN` - `403 Forbidden`.
+/// * Malformed or unsupported credentials - `400 Bad Request`.
+/// * Unreachable identity provider - `503 Service Unavailable`.
+pub fn status_code(error: &Error) -> StatusCode {
+    error_response::status_code(error)
+}
+
+///
+/// Builds the `application/problem+json` response for an authorization error, including the
+/// `WWW-Authenticate` header as described by RFC 6750 and RFC 9449. Requests without any token
+/// get a challenge without error code, while server errors get no challenge.
+///
+/// # Arguments
+///
+/// * `error` - Error obtained while authorizing the request.
+pub fn error_response(error: &Error) -> Response {
+    let status_code = status_code(error);
+    let mut response = error.clone().into_response();
+
+    if status_code.is_server_error() {
+        return response;
+    }
+
+    if *error.kind() == ErrorKind::MissingCredentials {
+        response
+            .headers_mut()
+            .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+
+        return response;
+    }
+
+    let (scheme, error_code) = match (error.kind(), status_code) {
+        (ErrorKind::InvalidDpopProof, _) => ("DPoP", "invalid_dpop_proof"),
+        (_, StatusCode::UNAUTHORIZED) => ("Bearer", "invalid_token"),
+        (_, StatusCode::FORBIDDEN) => ("Bearer", "insufficient_scope"),
+        _ => ("Bearer", "invalid_request"),
+    };
+    let description: String = error
+        .message()
+        .chars()
+        .filter(|c| c.is_ascii() && !c.is_ascii_control() && *c != '"' && *c != '\\')
+        .collect();
+    let challenge = HeaderValue::from_str(
+        format!(
+            "{} error=\"{}\", error_description=\"{}\"",
+            scheme, error_code, description
+        )
+        .as_str(),
+    )
+    .unwrap_or(HeaderValue::from_static("Bearer"));
+
+    response.headers_mut().insert(WWW_AUTHENTICATE, challenge);
+
+    response
+}
+
 #[derive(Clone)]
 pub struct Authorization {
-    token_validator: Arc<dyn TokenValidator + Send + Sync>,
+    token_validator: Arc<dyn AsyncTokenValidator + Send + Sync>,
+    revocation_store: Option<Arc<dyn RevocationStore + Send + Sync>>,
+    token_cookie: Option<String>,
+    token_query_parameter: Option<String>,
+    dpop_validator: Option<Arc<DpopValidator>>,
+    client_certificate_mode: Option<ClientCertificateMode>,
+    api_key: Option<(String, Arc<dyn AsyncTokenValidator + Send + Sync>)>,
+    audit_sinks: Vec<Arc<dyn AuditSink + Send + Sync>>,
 }
 
 impl Authorization {
+    /// Creates an `Authorization` validating tokens synchronously. Validators which may wait for
+    /// I/O, i.e. a `JwtTokenValidator` refreshing its keys, should be used with `new_async`.
     pub fn new(token_validator: Arc<dyn TokenValidator + Send + Sync>) -> Self {
-        Self { token_validator }
+        Self::new_async(Arc::new(token_validator))
+    }
+
+    /// Creates an `Authorization` whose validator may perform I/O, i.e. token introspection.
+    pub fn new_async(token_validator: Arc<dyn AsyncTokenValidator + Send + Sync>) -> Self {
+        Self {
+            token_validator,
+            revocation_store: None,
+            token_cookie: None,
+            token_query_parameter: None,
+            dpop_validator: None,
+            client_certificate_mode: None,
+            api_key: None,
+            audit_sinks: Vec::new(),
+        }
+    }
+
+    /// Rejects the tokens revoked within `revocation_store`, even if they are otherwise valid.
+    pub fn with_revocation_store(
+        mut self,
+        revocation_store: Arc<dyn RevocationStore + Send + Sync>,
+    ) -> Self {
+        self.revocation_store = Some(revocation_store);
+        self
+    }
+
+    /// Also looks up the bearer token within the cookie `cookie_name`, i.e. for browser clients.
+    pub fn with_token_cookie(mut self, cookie_name: impl Into<String>) -> Self {
+        self.token_cookie = Some(cookie_name.into());
+        self
+    }
+
+    /// Also looks up the bearer token within the query parameter `parameter_name`, i.e. for
+    /// WebSocket or server-sent events clients which cannot set headers.
+    pub fn with_token_query_parameter(mut self, parameter_name: impl Into<String>) -> Self {
+        self.token_query_parameter = Some(parameter_name.into());
+        self
+    }
+
+    /// Accepts sender-constrained tokens sent with the `DPoP` scheme along with a `DPoP` proof.
+    /// DPoP-bound tokens sent with the `Bearer` scheme are rejected from then on.
+    pub fn with_dpop(mut self, dpop_validator: Arc<DpopValidator>) -> Self {
+        self.dpop_validator = Some(dpop_validator);
+        self
+    }
+
+    /// Uses the `ClientCertificate` inserted into the request extensions by the server layer,
+    /// either only for certificate-bound tokens or also as the identity of requests without
+    /// token, depending on `client_certificate_mode`.
+    pub fn with_client_certificates(
+        mut self,
+        client_certificate_mode: ClientCertificateMode,
+    ) -> Self {
+        self.client_certificate_mode = Some(client_certificate_mode);
+        self
+    }
+
+    ///
+    /// Also accepts API keys sent within the header `header_name`, i.e. `X-Api-Key`.
+    ///
+    /// # Arguments
+    ///
+    /// * `header_name` - Name of the header containing the API key.
+    /// * `api_key_validator` - Validator of the API keys, i.e. `ApiKeyValidator`.
+    pub fn with_api_key_header(
+        mut self,
+        header_name: impl Into<String>,
+        api_key_validator: Arc<dyn AsyncTokenValidator + Send + Sync>,
+    ) -> Self {
+        self.api_key = Some((header_name.into(), api_key_validator));
+        self
+    }
+
+    /// Reports the outcome of every authentication and authorization to `audit_sink`, i.e.
+    /// `LogAuditSink` or `AuditCounters`. Several sinks may be added.
+    pub fn with_audit_sink(mut self, audit_sink: Arc<dyn AuditSink + Send + Sync>) -> Self {
+        self.audit_sinks.push(audit_sink);
+        self
     }
 
     ///
@@ -48,47 +226,337 @@ impl Authorization {
     ///
     /// # Returns
     ///
-    /// * `Ok` - If the headers contain a valid authorization header with a valid token.
-    /// * `Err` - If the validation failed.
-    pub async fn validate(&self, headers: HeaderMap) -> Result<(), Error> {
-        let authorization_value = some_or_return_error!(
-            headers.get(AUTHORIZATION_HEADER),
-            INVALID_TOKEN,
-            "'Authorization' header is missing"
+    /// * `Ok` - Validated token, if the headers contain a valid authorization header.
+    /// * `Err` - If the validation failed. DPoP proofs are checked against a `GET /` request,
+    ///   so `validate_parts` must be used for DPoP.
+    pub async fn validate(
+        &self,
+        headers: HeaderMap,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let (mut parts, _) = Request::new(()).into_parts();
+        parts.headers = headers;
+
+        self.validate_parts(&parts).await
+    }
+
+    ///
+    /// Checks if the request contains a valid token, either within the `Authorization` header
+    /// or within the configured cookie, query parameter or API key header. If configured,
+    /// requests without token are authenticated by their client certificate.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - Head of the request.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validated token.
+    /// * `Err` - Error with the kind `MISSING_CREDENTIALS` if the request has no token,
+    ///   `MULTIPLE_CREDENTIALS` if it has more than one, `MALFORMED_CREDENTIALS` or
+    ///   `UNSUPPORTED_AUTHORIZATION_SCHEME` if the `Authorization` header cannot be used,
+    ///   `INVALID_DPOP_PROOF` if the DPoP proof is not valid, `INVALID_TOKEN` if the token is
+    ///   bound to another client certificate, otherwise the error of the token validation.
+    pub async fn validate_parts(
+        &self,
+        parts: &Parts,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let result = self.authenticate(parts).await;
+
+        match result {
+            Ok(ref token) => self.audit(parts, Some(token.as_ref()), None),
+            Err(ref error) => self.audit(parts, None, Some(error)),
+        }
+
+        result
+    }
+
+    ///
+    /// Validates the token of the `Authorization` header and checks it fulfills the `policy`.
+    ///
+    /// # Arguments
+    ///
+    /// * `headers` - Map of the headers of the request.
+    /// * `policy` - Requirements the token must fulfill.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validated token which fulfills the policy.
+    /// * `Err` - Error with the kind `FORBIDDEN` if the token does not fulfill the policy,
+    ///   otherwise the error of the token validation.
+    pub async fn authorize(
+        &self,
+        headers: HeaderMap,
+        policy: &Policy,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let (mut parts, _) = Request::new(()).into_parts();
+        parts.headers = headers;
+
+        self.authorize_parts(&parts, policy).await
+    }
+
+    /// Same as `authorize`, but also looks up the token within the configured cookie or
+    /// query parameter of the request.
+    pub async fn authorize_parts(
+        &self,
+        parts: &Parts,
+        policy: &Policy,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let token = match self.authenticate(parts).await {
+            Ok(token) => token,
+            Err(error) => {
+                self.audit(parts, None, Some(&error));
+                return Err(error);
+            }
+        };
+
+        if let Err(error) = policy.evaluate(token.as_ref()) {
+            self.audit(parts, Some(token.as_ref()), Some(&error));
+            return Err(error);
+        }
+
+        self.audit(parts, Some(token.as_ref()), None);
+
+        Ok(token)
+    }
+
+    async fn authenticate(&self, parts: &Parts) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let token = match self.credentials(parts) {
+            Ok(Credentials::Bearer(token)) => self.validate_bearer(token.as_str()).await?,
+            Ok(Credentials::DPoP(token)) => self.validate_dpop(parts, token.as_str()).await?,
+            Ok(Credentials::ApiKey(api_key)) => self.validate_api_key(api_key.as_str()).await?,
+            Err(error) if *error.kind() == ErrorKind::MissingCredentials => {
+                self.client_certificate_identity(parts, error)?
+            }
+            Err(error) => return Err(error),
+            Ok(credentials) => {
+                return Err(Error::new(
+                    UNSUPPORTED_AUTHORIZATION_SCHEME,
+                    format!(
+                        "authorization scheme '{}' is not accepted",
+                        credentials.scheme()
+                    ),
+                ))
+            }
+        };
+
+        if self.client_certificate_mode.is_some() {
+            verify_certificate_binding(
+                token.as_ref(),
+                parts.extensions.get::<ClientCertificate>(),
+            )?;
+        }
+
+        if let Some(ref revocation_store) = self.revocation_store {
+            if revocation_store.is_revoked(token.as_ref()).await? {
+                return Err(Error::new(REVOKED_TOKEN, "token has been revoked"));
+            }
+        }
+
+        Ok(token)
+    }
+
+    fn audit(
+        &self,
+        parts: &Parts,
+        token: Option<&(dyn Token + Send + Sync)>,
+        error: Option<&Error>,
+    ) {
+        if self.audit_sinks.is_empty() {
+            return;
+        }
+
+        let credentials = self.credentials(parts).ok();
+        let event = AuditEvent::new(parts, credentials.as_ref(), token, error);
+
+        for audit_sink in self.audit_sinks.iter() {
+            audit_sink.record(&event);
+        }
+    }
+
+    async fn validate_bearer(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let token = self.token_validator.validate(token).await?;
+
+        if self.dpop_validator.is_some() && jwk_thumbprint_confirmation(token.as_ref()).is_some() {
+            return Err(Error::new(
+                INVALID_TOKEN,
+                "DPoP-bound token must be sent with the 'DPoP' scheme",
+            ));
+        }
+
+        Ok(token)
+    }
+
+    async fn validate_dpop(
+        &self,
+        parts: &Parts,
+        token: &str,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let dpop_validator = some_or_return_error!(
+            self.dpop_validator.as_ref(),
+            UNSUPPORTED_AUTHORIZATION_SCHEME,
+            "authorization scheme 'DPoP' is not accepted"
         );
 
-        let authorization = ok_or_return_error!(
-            authorization_value.to_str(),
-            INVALID_TOKEN,
-            "could not read 'Authorization' header as string"
+        let proof = dpop_validator.verify_request(parts, token)?;
+        let token = self.token_validator.validate(token).await?;
+
+        verify_binding(token.as_ref(), &proof)?;
+        // the proof is only used up once the token has been accepted as well
+        dpop_validator.register(&proof)?;
+
+        Ok(token)
+    }
+
+    async fn validate_api_key(&self, api_key: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let (_, api_key_validator) = some_or_return_error!(
+            self.api_key.as_ref(),
+            UNSUPPORTED_AUTHORIZATION_SCHEME,
+            "API keys are not accepted"
         );
 
-        // skip 'Bearer '
-        if authorization.len() < 7 {
+        api_key_validator.validate(api_key).await
+    }
+
+    fn client_certificate_identity(
+        &self,
+        parts: &Parts,
+        missing_credentials: Error,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        match (
+            self.client_certificate_mode,
+            parts.extensions.get::<ClientCertificate>(),
+        ) {
+            (Some(ClientCertificateMode::Identity), Some(client_certificate)) => {
+                Ok(Arc::new(client_certificate.token()))
+            }
+            _ => Err(missing_credentials),
+        }
+    }
+
+    // as per RFC 6750, clients must not use more than one method to transmit the token
+    fn credentials(&self, parts: &Parts) -> Result<Credentials, Error> {
+        let mut credentials: Vec<Credentials> = Vec::new();
+
+        for authorization_value in parts.headers.get_all(AUTHORIZATION_HEADER) {
+            let authorization = ok_or_return_error!(
+                authorization_value.to_str(),
+                MALFORMED_CREDENTIALS,
+                "could not read 'Authorization' header as string: "
+            );
+
+            credentials.push(Credentials::parse(authorization)?);
+        }
+
+        if let Some(ref cookie_name) = self.token_cookie {
+            for token in cookie_values(&parts.headers, cookie_name) {
+                credentials.push(bearer(token, "cookie")?);
+            }
+        }
+
+        if let Some((ref header_name, _)) = self.api_key {
+            for api_key_value in parts.headers.get_all(header_name.as_str()) {
+                let api_key = ok_or_return_error!(
+                    api_key_value.to_str(),
+                    MALFORMED_CREDENTIALS,
+                    format!("could not read '{}' header as string: ", header_name)
+                );
+
+                credentials.push(Credentials::ApiKey(api_key.trim().to_string()));
+            }
+        }
+
+        if let Some(ref parameter_name) = self.token_query_parameter {
+            for token in query_values(&parts.uri, parameter_name)? {
+                credentials.push(bearer(token, "query parameter")?);
+            }
+        }
+
+        if credentials.len() > 1 {
             return Err(Error::new(
-                INVALID_TOKEN,
-                "'Authorization' header value is invalid",
+                MULTIPLE_CREDENTIALS,
+                "request contains more than one token",
             ));
         }
 
-        let token = &authorization[7..];
+        let credentials = some_or_return_error!(
+            credentials.pop(),
+            MISSING_CREDENTIALS,
+            "request does not contain a token"
+        );
 
-        self.token_validator.validate(token)?;
+        Ok(credentials)
+    }
+}
 
-        Ok(())
+fn bearer(token: String, source: &str) -> Result<Credentials, Error> {
+    if token.is_empty() {
+        return Err(Error::new(
+            MALFORMED_CREDENTIALS,
+            format!("token {} is empty", source),
+        ));
     }
+
+    Ok(Credentials::Bearer(token))
+}
+
+fn cookie_values(headers: &HeaderMap, cookie_name: &str) -> Vec<String> {
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|cookie| cookie.to_str().ok())
+        .flat_map(|cookie| cookie.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .filter(|(name, _)| *name == cookie_name)
+        .map(|(_, value)| value.trim_matches('"').to_string())
+        .collect()
+}
+
+fn query_values(uri: &Uri, parameter_name: &str) -> Result<Vec<String>, Error> {
+    let Query(parameters) = ok_or_return_error!(
+        Query::<Vec<(String, String)>>::try_from_uri(uri),
+        MALFORMED_CREDENTIALS,
+        "could not parse the query of the request: "
+    );
+
+    Ok(parameters
+        .into_iter()
+        .filter(|(name, _)| name == parameter_name)
+        .map(|(_, value)| value)
+        .collect())
 }
 
 #[cfg(test)]
 pub mod tests {
     use std::sync::Arc;
 
-    use axum::http::{HeaderMap, HeaderValue};
+    use axum::extract::Request;
+    use axum::http::header::{CONTENT_TYPE, COOKIE, WWW_AUTHENTICATE};
+    use axum::http::request::Parts;
+    use axum::http::{HeaderMap, HeaderValue, StatusCode};
+    use axum::response::Response;
     use mockall::predicate::eq;
+    use serde_json::json;
 
-    use crate::auth::authorization::{Authorization, AUTHORIZATION_HEADER};
+    use crate::auth::api_key_validator::{ApiKey, ApiKeyValidator, API_KEY_HEADER};
+    use crate::auth::async_token_validator::MockAsyncTokenValidator;
+    use crate::auth::audit::AuditCounters;
+    use crate::auth::authorization::{
+        error_response, status_code, Authorization, AUTHORIZATION_HEADER,
+    };
+    use crate::auth::claims_token::ClaimsToken;
+    use crate::auth::client_certificate::{ClientCertificate, ClientCertificateMode};
+    use crate::auth::dpop::tests::{encode_proof, get_proof_claims, get_public_jwk, ACCESS_TOKEN};
+    use crate::auth::dpop::{jwk_thumbprint, DpopValidator, DPOP_HEADER};
+    use crate::auth::error_kind::{
+        FORBIDDEN, INVALID_DPOP_PROOF, INVALID_TOKEN, MALFORMED_CREDENTIALS, MISSING_CREDENTIALS,
+        MULTIPLE_CREDENTIALS, REVOKED_TOKEN, UNSUPPORTED_AUTHORIZATION_SCHEME,
+    };
+    use crate::auth::policy::Policy;
+    use crate::auth::revocation_store::InMemoryRevocationStore;
     use crate::auth::token::MockToken;
     use crate::auth::token_validator::MockTokenValidator;
+    use crate::error::Error;
+    use crate::error_response::PROBLEM_JSON_CONTENT_TYPE;
 
     #[tokio::test]
     pub async fn validate_extracts_token_from_header() {
@@ -114,4 +582,587 @@ pub mod tests {
 
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    pub async fn validate_lowercase_scheme_extracts_token() {
+        let authorization = get_authorization_expecting("1234abcd");
+        let mut headers = HeaderMap::new();
+        headers.append(
+            AUTHORIZATION_HEADER,
+            HeaderValue::from_static("bearer 1234abcd"),
+        );
+
+        let result = authorization.validate(headers).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_without_credentials_returns_missing_credentials() {
+        let authorization = get_authorization_expecting("1234abcd");
+
+        let error = authorization
+            .validate(HeaderMap::new())
+            .await
+            .expect_err("expected missing credentials");
+
+        assert_eq!(MISSING_CREDENTIALS, error.error_kind());
+        let response = error_response(&error);
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+        assert_eq!(
+            Some(&HeaderValue::from_static("Bearer")),
+            response.headers().get(WWW_AUTHENTICATE)
+        );
+    }
+
+    #[tokio::test]
+    pub async fn validate_invalid_header_returns_precise_error_kind() {
+        let authorization = get_authorization_expecting("1234abcd");
+        let cases = [
+            (
+                HeaderValue::from_static("Basic Y2xpZW50OnNlY3JldA=="),
+                UNSUPPORTED_AUTHORIZATION_SCHEME,
+            ),
+            (
+                HeaderValue::from_static("Digest 1234abcd"),
+                UNSUPPORTED_AUTHORIZATION_SCHEME,
+            ),
+            (HeaderValue::from_static("Bearer "), MALFORMED_CREDENTIALS),
+            (HeaderValue::from_static("Bearer"), MALFORMED_CREDENTIALS),
+            (
+                HeaderValue::from_bytes("Bearer 1234ÿabcd".as_bytes())
+                    .expect("expected non-ASCII HeaderValue"),
+                MALFORMED_CREDENTIALS,
+            ),
+            (
+                HeaderValue::from_bytes("ÿÿÿÿÿÿÿÿ".as_bytes())
+                    .expect("expected non-ASCII HeaderValue"),
+                MALFORMED_CREDENTIALS,
+            ),
+        ];
+
+        for (authorization_header, expected_error_kind) in cases {
+            let mut headers = HeaderMap::new();
+            headers.append(AUTHORIZATION_HEADER, authorization_header);
+
+            let error = authorization
+                .validate(headers)
+                .await
+                .expect_err("expected invalid header");
+
+            assert_eq!(expected_error_kind, error.error_kind());
+            assert_eq!(StatusCode::BAD_REQUEST, status_code(&error));
+        }
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_reads_token_from_cookie() {
+        let authorization =
+            get_authorization_expecting("1234abcd").with_token_cookie("access_token");
+        let parts = get_parts("/", Some("theme=dark; access_token=1234abcd"), None);
+
+        let result = authorization.validate_parts(&parts).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_reads_token_from_query_parameter() {
+        let authorization =
+            get_authorization_expecting("1234abcd").with_token_query_parameter("access_token");
+        let parts = get_parts("/events?stream=1&access_token=1234abcd", None, None);
+
+        let result = authorization.validate_parts(&parts).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_several_tokens_returns_multiple_credentials() {
+        let authorization = get_authorization_expecting("1234abcd")
+            .with_token_cookie("access_token")
+            .with_token_query_parameter("access_token");
+        let cases = [
+            get_parts("/?access_token=1234abcd", None, Some("Bearer 1234abcd")),
+            get_parts("/", Some("access_token=1234abcd"), Some("Bearer 1234abcd")),
+            get_parts("/?access_token=1234abcd&access_token=1234abcd", None, None),
+        ];
+
+        for parts in cases {
+            let error = authorization
+                .validate_parts(&parts)
+                .await
+                .expect_err("expected multiple credentials");
+
+            assert_eq!(MULTIPLE_CREDENTIALS, error.error_kind());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_ignores_unconfigured_sources() {
+        let authorization = get_authorization_expecting("1234abcd");
+        let parts = get_parts(
+            "/?access_token=other",
+            Some("access_token=other"),
+            Some("Bearer 1234abcd"),
+        );
+
+        let result = authorization.validate_parts(&parts).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_dpop_bound_token_succeeds() {
+        let authorization =
+            get_authorization_returning_bound_token().with_dpop(Arc::new(DpopValidator::default()));
+        let parts = get_dpop_parts(
+            format!("DPoP {}", ACCESS_TOKEN).as_str(),
+            Some(encode_proof(get_proof_claims()).as_str()),
+        );
+
+        let result = authorization.validate_parts(&parts).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_invalid_dpop_proof_returns_dpop_challenge() {
+        let authorization =
+            get_authorization_returning_bound_token().with_dpop(Arc::new(DpopValidator::default()));
+        let mut claims = get_proof_claims();
+        claims["htm"] = "POST".into();
+        let parts = get_dpop_parts(
+            format!("DPoP {}", ACCESS_TOKEN).as_str(),
+            Some(encode_proof(claims).as_str()),
+        );
+
+        let error = authorization
+            .validate_parts(&parts)
+            .await
+            .expect_err("expected invalid proof");
+
+        assert_eq!(INVALID_DPOP_PROOF, error.error_kind());
+        let response = error_response(&error);
+        assert_eq!(StatusCode::UNAUTHORIZED, response.status());
+        assert!(response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .expect("expected 'WWW-Authenticate' header")
+            .starts_with("DPoP error=\"invalid_dpop_proof\""));
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_rejected_dpop_token_does_not_use_up_proof() {
+        let dpop_validator = Arc::new(DpopValidator::default());
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock
+            .expect_validate()
+            .returning(|_| Err(Error::new(INVALID_TOKEN, "expired token")));
+        let rejecting_authorization =
+            Authorization::new(Arc::new(token_validator_mock)).with_dpop(dpop_validator.clone());
+        let authorization = get_authorization_returning_bound_token().with_dpop(dpop_validator);
+        let proof = encode_proof(get_proof_claims());
+        let parts = get_dpop_parts(
+            format!("DPoP {}", ACCESS_TOKEN).as_str(),
+            Some(proof.as_str()),
+        );
+
+        let error = rejecting_authorization
+            .validate_parts(&parts)
+            .await
+            .expect_err("expected rejected token");
+        let result = authorization.validate_parts(&parts).await;
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_dpop_bound_bearer_token_returns_invalid_token() {
+        let authorization =
+            get_authorization_returning_bound_token().with_dpop(Arc::new(DpopValidator::default()));
+        let parts = get_dpop_parts(format!("Bearer {}", ACCESS_TOKEN).as_str(), None);
+
+        let error = authorization
+            .validate_parts(&parts)
+            .await
+            .expect_err("expected downgraded token");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_dpop_scheme_without_dpop_returns_unsupported_scheme() {
+        let authorization = get_authorization_returning_bound_token();
+        let parts = get_dpop_parts(
+            format!("DPoP {}", ACCESS_TOKEN).as_str(),
+            Some(encode_proof(get_proof_claims()).as_str()),
+        );
+
+        let error = authorization
+            .validate_parts(&parts)
+            .await
+            .expect_err("expected unsupported scheme");
+
+        assert_eq!(UNSUPPORTED_AUTHORIZATION_SCHEME, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_certificate_bound_token_checks_certificate() {
+        let client_certificate = ClientCertificate::new(b"certificate".to_vec(), "service-a");
+        let other_certificate = ClientCertificate::new(b"other".to_vec(), "service-b");
+        let authorization =
+            get_authorization_returning_certificate_bound_token(client_certificate.thumbprint())
+                .with_client_certificates(ClientCertificateMode::BoundTokens);
+
+        let result = authorization
+            .validate_parts(&get_certificate_parts(
+                Some("Bearer 1234abcd"),
+                Some(client_certificate),
+            ))
+            .await;
+        assert!(result.is_ok());
+
+        for presented_certificate in [Some(other_certificate), None] {
+            let error = authorization
+                .validate_parts(&get_certificate_parts(
+                    Some("Bearer 1234abcd"),
+                    presented_certificate,
+                ))
+                .await
+                .expect_err("expected certificate binding failure");
+
+            assert_eq!(INVALID_TOKEN, error.error_kind());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_client_certificate_identity_returns_certificate_token() {
+        let client_certificate = ClientCertificate::new(b"certificate".to_vec(), "service-a");
+        let authorization = Authorization::new(Arc::new(MockTokenValidator::new()))
+            .with_client_certificates(ClientCertificateMode::Identity);
+
+        let token = authorization
+            .validate_parts(&get_certificate_parts(None, Some(client_certificate)))
+            .await
+            .expect("expected client certificate identity");
+
+        assert_eq!(Some("service-a".to_string()), token.subject());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_without_identity_mode_ignores_client_certificate() {
+        let client_certificate = ClientCertificate::new(b"certificate".to_vec(), "service-a");
+        let authorization = Authorization::new(Arc::new(MockTokenValidator::new()))
+            .with_client_certificates(ClientCertificateMode::BoundTokens);
+
+        let error = authorization
+            .validate_parts(&get_certificate_parts(None, Some(client_certificate)))
+            .await
+            .expect_err("expected missing credentials");
+
+        assert_eq!(MISSING_CREDENTIALS, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_api_key_header_returns_owner() {
+        let authorization = get_authorization_expecting("1234abcd")
+            .with_api_key_header(API_KEY_HEADER, get_api_key_validator());
+        let mut parts = get_parts("/", None, None);
+        parts
+            .headers
+            .insert(API_KEY_HEADER, HeaderValue::from_static("billing-key"));
+
+        let token = authorization
+            .validate_parts(&parts)
+            .await
+            .expect("expected valid API key");
+
+        assert_eq!(Some("billing-service".to_string()), token.subject());
+    }
+
+    #[tokio::test]
+    pub async fn validate_parts_with_api_key_and_bearer_token_returns_multiple_credentials() {
+        let authorization = get_authorization_expecting("1234abcd")
+            .with_api_key_header(API_KEY_HEADER, get_api_key_validator());
+        let mut parts = get_parts("/", None, Some("Bearer 1234abcd"));
+        parts
+            .headers
+            .insert(API_KEY_HEADER, HeaderValue::from_static("billing-key"));
+
+        let error = authorization
+            .validate_parts(&parts)
+            .await
+            .expect_err("expected multiple credentials");
+
+        assert_eq!(MULTIPLE_CREDENTIALS, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn validate_with_async_validator_returns_token() {
+        let mut token_validator_mock = MockAsyncTokenValidator::new();
+        token_validator_mock
+            .expect_validate()
+            .with(eq("1234abcd"))
+            .times(1)
+            .returning(|_| Ok(Arc::new(MockToken::new())));
+        let authorization = Authorization::new_async(Arc::new(token_validator_mock));
+
+        let result = authorization.validate(get_headers()).await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_revoked_token_returns_error() {
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock.expect_validate().returning(|_| {
+            let mut token_mock = MockToken::new();
+            token_mock
+                .expect_token_id()
+                .returning(|| Some("revoked-jti".to_string()));
+
+            Ok(Arc::new(token_mock))
+        });
+        let revocation_store = InMemoryRevocationStore::default();
+        revocation_store.revoke_token_id("revoked-jti");
+        let authorization = Authorization::new(Arc::new(token_validator_mock))
+            .with_revocation_store(Arc::new(revocation_store));
+
+        let error = authorization
+            .validate(get_headers())
+            .await
+            .expect_err("expected revoked token");
+
+        assert_eq!(REVOKED_TOKEN, error.error_kind());
+        assert_eq!(StatusCode::UNAUTHORIZED, status_code(&error));
+    }
+
+    #[tokio::test]
+    pub async fn authorize_token_with_required_scope_succeeds() {
+        let authorization = get_authorization_returning_scope("read write");
+
+        let result = authorization
+            .authorize(get_headers(), &Policy::scope("write"))
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn authorize_token_without_required_scope_returns_forbidden() {
+        let authorization = get_authorization_returning_scope("read");
+
+        let error = authorization
+            .authorize(get_headers(), &Policy::scope("write"))
+            .await
+            .expect_err("expected forbidden error");
+
+        assert_eq!(FORBIDDEN, error.error_kind());
+        assert_eq!(StatusCode::FORBIDDEN, status_code(&error));
+    }
+
+    #[tokio::test]
+    pub async fn audit_sink_records_every_outcome() {
+        let audit_counters = Arc::new(AuditCounters::default());
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock
+            .expect_validate()
+            .returning(|token| match token {
+                "valid" => Ok(Arc::new(ClaimsToken::new(
+                    serde_json::from_value(json!({ "sub": "user-1", "scope": "read" }))
+                        .expect("expected claims map"),
+                ))),
+                _ => Err(Error::new(INVALID_TOKEN, "token is not valid")),
+            });
+        let authorization = Authorization::new(Arc::new(token_validator_mock))
+            .with_audit_sink(audit_counters.clone());
+
+        for (authorization_header, policy) in [
+            (Some("Bearer valid"), None),
+            (Some("Bearer invalid"), None),
+            (None, None),
+            (Some("Bearer valid"), Some(Policy::scope("write"))),
+        ] {
+            let parts = get_parts("/", None, authorization_header);
+            let _ = match policy {
+                Some(ref policy) => authorization.authorize_parts(&parts, policy).await,
+                None => authorization.validate_parts(&parts).await,
+            };
+        }
+
+        assert_eq!(1, audit_counters.successes());
+        assert_eq!(1, audit_counters.failures(INVALID_TOKEN));
+        assert_eq!(1, audit_counters.failures(MISSING_CREDENTIALS));
+        assert_eq!(1, audit_counters.failures(FORBIDDEN));
+    }
+
+    #[tokio::test]
+    pub async fn authorize_macro_returns_forbidden_error_response() {
+        let authorization = get_authorization_returning_scope("read");
+
+        let result = authorized_handler(authorization, get_headers()).await;
+
+        let response = result.expect_err("expected forbidden response");
+        assert_eq!(StatusCode::FORBIDDEN, response.status());
+        assert_eq!(
+            Some(PROBLEM_JSON_CONTENT_TYPE),
+            response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+        );
+        assert!(response
+            .headers()
+            .get(WWW_AUTHENTICATE)
+            .and_then(|challenge| challenge.to_str().ok())
+            .expect("expected 'WWW-Authenticate' header")
+            .starts_with("Bearer error=\"insufficient_scope\""));
+    }
+
+    #[test]
+    pub fn status_code_maps_error_kinds() {
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            status_code(&Error::new(INVALID_TOKEN, ""))
+        );
+        assert_eq!(
+            StatusCode::BAD_REQUEST,
+            status_code(&Error::new("malformed_token", ""))
+        );
+    }
+
+    async fn authorized_handler(
+        authorization: Authorization,
+        headers: HeaderMap,
+    ) -> Result<Vec<String>, Response> {
+        let token = authorize!(authorization, headers, Policy::scope("write"));
+
+        Ok(token.scopes())
+    }
+
+    fn get_authorization_expecting(expected_token: &'static str) -> Authorization {
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock
+            .expect_validate()
+            .with(eq(expected_token))
+            .returning(|_| Ok(Arc::new(MockToken::new())));
+
+        Authorization::new(Arc::new(token_validator_mock))
+    }
+
+    fn get_api_key_validator() -> Arc<ApiKeyValidator> {
+        let api_key_validator = ApiKeyValidator::new(vec![ApiKey::from_key(
+            "billing-service",
+            "billing-key",
+            vec!["invoices:read".to_string()],
+        )])
+        .expect("expected API key validator");
+
+        Arc::new(api_key_validator)
+    }
+
+    fn get_authorization_returning_bound_token() -> Authorization {
+        let thumbprint = jwk_thumbprint(&get_public_jwk()).expect("expected thumbprint");
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock
+            .expect_validate()
+            .with(eq(ACCESS_TOKEN))
+            .returning(move |_| {
+                let claims = serde_json::from_value(serde_json::json!({
+                    "sub": "user-1",
+                    "cnf": { "jkt": thumbprint }
+                }))
+                .expect("expected claims map");
+
+                Ok(Arc::new(ClaimsToken::new(claims)))
+            });
+
+        Authorization::new(Arc::new(token_validator_mock))
+    }
+
+    fn get_authorization_returning_certificate_bound_token(thumbprint: String) -> Authorization {
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock.expect_validate().returning(move |_| {
+            let claims = serde_json::from_value(serde_json::json!({
+                "sub": "service-a",
+                "cnf": { "x5t#S256": thumbprint }
+            }))
+            .expect("expected claims map");
+
+            Ok(Arc::new(ClaimsToken::new(claims)))
+        });
+
+        Authorization::new(Arc::new(token_validator_mock))
+    }
+
+    fn get_certificate_parts(
+        authorization: Option<&str>,
+        client_certificate: Option<ClientCertificate>,
+    ) -> Parts {
+        let mut parts = get_parts("/", None, authorization);
+
+        if let Some(client_certificate) = client_certificate {
+            parts.extensions.insert(client_certificate);
+        }
+
+        parts
+    }
+
+    fn get_dpop_parts(authorization: &str, proof: Option<&str>) -> Parts {
+        let mut builder = Request::builder()
+            .uri("/resources")
+            .header("Host", "api.example.com")
+            .header(AUTHORIZATION_HEADER, authorization);
+
+        if let Some(proof) = proof {
+            builder = builder.header(DPOP_HEADER, proof);
+        }
+
+        let (parts, _) = builder.body(()).expect("expected request").into_parts();
+
+        parts
+    }
+
+    fn get_parts(uri: &str, cookie: Option<&str>, authorization: Option<&str>) -> Parts {
+        let mut builder = Request::builder().uri(uri);
+
+        if let Some(cookie) = cookie {
+            builder = builder.header(COOKIE, cookie);
+        }
+
+        if let Some(authorization) = authorization {
+            builder = builder.header(AUTHORIZATION_HEADER, authorization);
+        }
+
+        let (parts, _) = builder.body(()).expect("expected request").into_parts();
+
+        parts
+    }
+
+    fn get_authorization_returning_scope(scope: &'static str) -> Authorization {
+        let mut token_validator_mock = MockTokenValidator::new();
+        token_validator_mock.expect_validate().returning(move |_| {
+            let mut token_mock = MockToken::new();
+            token_mock
+                .expect_scopes()
+                .returning(move || scope.split(' ').map(String::from).collect());
+
+            Ok(Arc::new(token_mock))
+        });
+
+        Authorization::new(Arc::new(token_validator_mock))
+    }
+
+    fn get_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            AUTHORIZATION_HEADER,
+            HeaderValue::from_static("Bearer 1234abcd"),
+        );
+
+        headers
+    }
 }
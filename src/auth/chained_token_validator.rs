@@ -0,0 +1,163 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::auth::async_token_validator::AsyncTokenValidator;
+use crate::auth::token::Token;
+use crate::error::Error;
+use crate::error_kind::{ErrorKind, Fault};
+
+/// `ChainedTokenValidator` tries its validators in order and accepts the token as soon as
+/// one of them does, i.e. for accepting both JWTs and opaque tokens. Use
+/// `CompositeTokenValidator` for routing tokens by issuer.
+pub struct ChainedTokenValidator {
+    token_validators: Vec<Arc<dyn AsyncTokenValidator + Send + Sync>>,
+}
+
+impl ChainedTokenValidator {
+    pub fn new(token_validators: Vec<Arc<dyn AsyncTokenValidator + Send + Sync>>) -> Self {
+        Self { token_validators }
+    }
+}
+
+#[async_trait]
+impl AsyncTokenValidator for ChainedTokenValidator {
+    async fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let mut errors: Vec<(String, Error)> = Vec::new();
+
+        for (index, token_validator) in self.token_validators.iter().enumerate() {
+            match token_validator.validate(token).await {
+                Ok(token) => return Ok(token),
+                Err(error) => errors.push((format!("validator {}", index + 1), error)),
+            }
+        }
+
+        Err(aggregate_errors(errors))
+    }
+}
+
+///
+/// Combines the errors of the validators which rejected a token. Server faults, i.e. an
+/// unreachable introspection endpoint, are kept over the rejections of the other validators,
+/// so an outage is not reported as an invalid token.
+///
+/// # Arguments
+///
+/// * `errors` - Name of each validator along with its error.
+///
+/// # Returns
+///
+/// * The only error, if there is one.
+/// * Otherwise, an error with the kind of the first server fault, or the kind shared by every
+///   error, or `INVALID_TOKEN`, whose message lists every error.
+pub(crate) fn aggregate_errors(mut errors: Vec<(String, Error)>) -> Error {
+    if errors.len() == 1 {
+        if let Some((_, error)) = errors.pop() {
+            return error;
+        }
+    }
+
+    let server_fault = errors
+        .iter()
+        .find(|(_, error)| error.fault() == Fault::Server);
+    let error_kind = match (server_fault, errors.first()) {
+        (Some((_, server_fault)), _) => server_fault.kind().clone(),
+        (None, Some((_, first)))
+            if errors.iter().all(|(_, error)| error.kind() == first.kind()) =>
+        {
+            first.kind().clone()
+        }
+        _ => ErrorKind::InvalidToken,
+    };
+
+    let messages: Vec<String> = errors
+        .iter()
+        .map(|(name, error)| format!("{}: {}", name, error))
+        .collect();
+
+    Error::new(
+        error_kind,
+        format!("no validator accepted the token: [{}]", messages.join("; ")),
+    )
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use crate::auth::async_token_validator::{AsyncTokenValidator, MockAsyncTokenValidator};
+    use crate::auth::chained_token_validator::ChainedTokenValidator;
+    use crate::auth::error_kind::{INTROSPECTION_FAILURE, INVALID_TOKEN, MALFORMED_TOKEN};
+    use crate::auth::token::MockToken;
+    use crate::auth::token_validator::MockTokenValidator;
+    use crate::error::Error;
+
+    #[tokio::test]
+    pub async fn validate_returns_first_accepted_token() {
+        let mut first = MockTokenValidator::new();
+        first
+            .expect_validate()
+            .times(1)
+            .returning(|_| Err(Error::new(MALFORMED_TOKEN, "not a JWT")));
+        let mut second = MockAsyncTokenValidator::new();
+        second
+            .expect_validate()
+            .times(1)
+            .returning(|_| Ok(Arc::new(MockToken::new())));
+        let mut third = MockTokenValidator::new();
+        third.expect_validate().times(0);
+        let token_validator =
+            ChainedTokenValidator::new(vec![Arc::new(first), Arc::new(second), Arc::new(third)]);
+
+        let result = token_validator.validate("token").await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    pub async fn validate_all_rejecting_returns_invalid_token() {
+        let mut first = MockTokenValidator::new();
+        first
+            .expect_validate()
+            .returning(|_| Err(Error::new(MALFORMED_TOKEN, "not a JWT")));
+        let mut second = MockTokenValidator::new();
+        second
+            .expect_validate()
+            .returning(|_| Err(Error::new(INVALID_TOKEN, "token is not active")));
+        let token_validator = ChainedTokenValidator::new(vec![Arc::new(first), Arc::new(second)]);
+
+        let error = token_validator
+            .validate("token")
+            .await
+            .expect_err("expected every validator to reject the token");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+        assert!(error.message().contains("not a JWT"));
+        assert!(error.message().contains("token is not active"));
+    }
+
+    #[tokio::test]
+    pub async fn validate_with_unreachable_introspection_returns_introspection_failure() {
+        let mut first = MockTokenValidator::new();
+        first
+            .expect_validate()
+            .returning(|_| Err(Error::new(MALFORMED_TOKEN, "not a JWT")));
+        let mut second = MockTokenValidator::new();
+        second
+            .expect_validate()
+            .returning(|_| Err(Error::new(INTROSPECTION_FAILURE, "connection refused")));
+        let token_validator = ChainedTokenValidator::new(vec![Arc::new(first), Arc::new(second)]);
+
+        let error = token_validator
+            .validate("token")
+            .await
+            .expect_err("expected the token not to be validated");
+
+        assert_eq!(INTROSPECTION_FAILURE, error.error_kind());
+        assert!(error.message().contains("validator 2"));
+    }
+}
@@ -0,0 +1,32 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::HashMap;
+
+use serde_json::Value;
+
+use crate::auth::token::Token;
+
+/// `ClaimsToken` is a token represented only by its claims, i.e. the response of a token
+/// introspection endpoint.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimsToken {
+    claims: HashMap<String, Value>,
+}
+
+impl ClaimsToken {
+    pub fn new(claims: HashMap<String, Value>) -> Self {
+        Self { claims }
+    }
+
+    pub fn claims(&self) -> &HashMap<String, Value> {
+        &self.claims
+    }
+}
+
+impl Token for ClaimsToken {
+    fn claim_value(&self, name: &str) -> Option<Value> {
+        self.claims.get(name).cloned()
+    }
+}
@@ -0,0 +1,180 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::auth::claims_token::ClaimsToken;
+use crate::auth::error_kind::INVALID_TOKEN;
+use crate::auth::token::{Token, CONFIRMATION_CLAIM, SUBJECT_CLAIM};
+use crate::error::Error;
+
+pub const CERTIFICATE_THUMBPRINT_CONFIRMATION: &str = "x5t#S256";
+
+/// `ClientCertificate` is the certificate presented by the client during the TLS handshake.
+///
+/// It is not extracted by `Authorization`: the server layer terminating TLS must verify the
+/// certificate chain and insert the `ClientCertificate` into the request extensions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClientCertificate {
+    der: Vec<u8>,
+    subject: String,
+}
+
+impl ClientCertificate {
+    ///
+    /// # Arguments
+    ///
+    /// * `der` - DER encoding of the verified certificate.
+    /// * `subject` - Identity of the client, i.e. the subject DN or a SAN of the certificate.
+    pub fn new(der: Vec<u8>, subject: impl Into<String>) -> Self {
+        Self {
+            der,
+            subject: subject.into(),
+        }
+    }
+
+    pub fn der(&self) -> &[u8] {
+        self.der.as_slice()
+    }
+
+    pub fn subject(&self) -> &str {
+        self.subject.as_str()
+    }
+
+    /// Base64url encoded SHA-256 thumbprint of the certificate, as used by `cnf.x5t#S256`.
+    pub fn thumbprint(&self) -> String {
+        URL_SAFE_NO_PAD.encode(Sha256::digest(self.der.as_slice()))
+    }
+
+    /// Token representing the client identity, having the certificate subject as `sub` and
+    /// being bound to the certificate through `cnf.x5t#S256`.
+    pub fn token(&self) -> ClaimsToken {
+        let mut claims: HashMap<String, Value> = HashMap::new();
+        claims.insert(SUBJECT_CLAIM.to_string(), json!(self.subject));
+        claims.insert(
+            CONFIRMATION_CLAIM.to_string(),
+            json!({ CERTIFICATE_THUMBPRINT_CONFIRMATION: self.thumbprint() }),
+        );
+
+        ClaimsToken::new(claims)
+    }
+}
+
+/// `ClientCertificateMode` tells `Authorization` how to use the client certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientCertificateMode {
+    /// Certificate-bound tokens (RFC 8705) are only accepted along with their certificate.
+    BoundTokens,
+    /// Same as `BoundTokens`, and requests without token are authenticated as the client
+    /// certificate's subject.
+    Identity,
+}
+
+///
+/// Checks the token is used along with the certificate it is bound to, if any.
+///
+/// # Arguments
+///
+/// * `token` - Validated access token.
+/// * `client_certificate` - Certificate presented by the client.
+///
+/// # Returns
+///
+/// * `Ok` - If the token is not certificate-bound, or it is bound to `client_certificate`.
+/// * `Err` - Error with the kind `INVALID_TOKEN` otherwise.
+pub fn verify_certificate_binding(
+    token: &(dyn Token + Send + Sync),
+    client_certificate: Option<&ClientCertificate>,
+) -> Result<(), Error> {
+    let expected_thumbprint = match certificate_thumbprint_confirmation(token) {
+        Some(thumbprint) => thumbprint,
+        None => return Ok(()),
+    };
+
+    match client_certificate {
+        Some(client_certificate) if client_certificate.thumbprint() == expected_thumbprint => {
+            Ok(())
+        }
+        Some(_) => Err(Error::new(
+            INVALID_TOKEN,
+            "token is bound to a different client certificate",
+        )),
+        None => Err(Error::new(
+            INVALID_TOKEN,
+            "token is bound to a client certificate which was not presented",
+        )),
+    }
+}
+
+/// Gets the `cnf.x5t#S256` claim of the token, present on certificate-bound tokens.
+pub fn certificate_thumbprint_confirmation(token: &(dyn Token + Send + Sync)) -> Option<String> {
+    match token.claim_value(CONFIRMATION_CLAIM) {
+        Some(Value::Object(confirmation)) => confirmation
+            .get(CERTIFICATE_THUMBPRINT_CONFIRMATION)
+            .and_then(|thumbprint| thumbprint.as_str())
+            .map(String::from),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::{json, Value};
+
+    use crate::auth::claims_token::ClaimsToken;
+    use crate::auth::client_certificate::{verify_certificate_binding, ClientCertificate};
+    use crate::auth::error_kind::INVALID_TOKEN;
+    use crate::auth::token::Token;
+
+    #[test]
+    pub fn thumbprint_is_base64url_sha256_of_der() {
+        let client_certificate = ClientCertificate::new(b"abc".to_vec(), "service-a");
+
+        assert_eq!(
+            "ungWv48Bz-pBQUDeXa4iI7ADYaOWF3qctBD_YfIAFa0",
+            client_certificate.thumbprint()
+        );
+    }
+
+    #[test]
+    pub fn token_is_bound_to_certificate() {
+        let client_certificate = ClientCertificate::new(b"abc".to_vec(), "service-a");
+
+        let token = client_certificate.token();
+
+        assert_eq!(Some("service-a".to_string()), token.subject());
+        assert!(verify_certificate_binding(&token, Some(&client_certificate)).is_ok());
+    }
+
+    #[test]
+    pub fn verify_certificate_binding_rejects_missing_or_other_certificate() {
+        let client_certificate = ClientCertificate::new(b"abc".to_vec(), "service-a");
+        let other_certificate = ClientCertificate::new(b"def".to_vec(), "service-b");
+        let token = client_certificate.token();
+
+        for presented_certificate in [Some(&other_certificate), None] {
+            let error = verify_certificate_binding(&token, presented_certificate)
+                .expect_err("expected binding failure");
+
+            assert_eq!(INVALID_TOKEN, error.error_kind());
+        }
+    }
+
+    #[test]
+    pub fn verify_certificate_binding_accepts_unbound_token() {
+        let claims: HashMap<String, Value> =
+            serde_json::from_value(json!({ "sub": "user-1" })).expect("expected claims map");
+
+        let result = verify_certificate_binding(&ClaimsToken::new(claims), None);
+
+        assert!(result.is_ok());
+    }
+}
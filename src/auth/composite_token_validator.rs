@@ -0,0 +1,365 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde_json::Value;
+
+use crate::auth::async_token_validator::AsyncTokenValidator;
+use crate::auth::chained_token_validator::aggregate_errors;
+use crate::auth::error_kind::INVALID_TOKEN;
+use crate::auth::jwt_token::unverified_issuer;
+use crate::auth::jwt_token_validator::JwtTokenValidator;
+use crate::auth::token::Token;
+use crate::error::Error;
+
+struct NamedTokenValidator {
+    name: String,
+    issuers: Vec<String>,
+    token_validator: Arc<dyn AsyncTokenValidator + Send + Sync>,
+}
+
+/// `CompositeTokenValidator` accepts tokens from several sources, i.e. two identity providers
+/// with different keys and audiences.
+///
+/// JWTs whose unverified `iss` claim matches the issuer of some validators are only given to
+/// those validators. Every other token is given, in order, to the validators registered
+/// without issuer. The accepted token reports the name of its validator through
+/// `Token::validated_by`.
+#[derive(Default)]
+pub struct CompositeTokenValidator {
+    token_validators: Vec<NamedTokenValidator>,
+}
+
+impl CompositeTokenValidator {
+    /// Adds a validator tried for the tokens not routed by issuer, i.e. opaque tokens.
+    pub fn with_validator(
+        mut self,
+        name: impl Into<String>,
+        token_validator: Arc<dyn AsyncTokenValidator + Send + Sync>,
+    ) -> Self {
+        self.token_validators.push(NamedTokenValidator {
+            name: name.into(),
+            issuers: Vec::new(),
+            token_validator,
+        });
+        self
+    }
+
+    /// Adds a validator for the tokens whose `iss` claim is `issuer`.
+    pub fn with_issuer_validator(
+        mut self,
+        name: impl Into<String>,
+        issuer: impl Into<String>,
+        token_validator: Arc<dyn AsyncTokenValidator + Send + Sync>,
+    ) -> Self {
+        self.token_validators.push(NamedTokenValidator {
+            name: name.into(),
+            issuers: vec![issuer.into()],
+            token_validator,
+        });
+        self
+    }
+
+    /// Adds a JWT validator for the tokens issued by any of its accepted issuers.
+    pub fn with_jwt_validator(
+        mut self,
+        name: impl Into<String>,
+        token_validator: JwtTokenValidator,
+    ) -> Self {
+        self.token_validators.push(NamedTokenValidator {
+            name: name.into(),
+            issuers: token_validator.issuers().to_vec(),
+            token_validator: Arc::new(token_validator),
+        });
+        self
+    }
+
+    fn candidates(&self, issuer: Option<&str>) -> Vec<&NamedTokenValidator> {
+        let routed: Vec<&NamedTokenValidator> = match issuer {
+            Some(issuer) => self
+                .token_validators
+                .iter()
+                .filter(|token_validator| {
+                    token_validator
+                        .issuers
+                        .iter()
+                        .any(|accepted| same_issuer(accepted, issuer))
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        if !routed.is_empty() {
+            return routed;
+        }
+
+        self.token_validators
+            .iter()
+            .filter(|token_validator| token_validator.issuers.is_empty())
+            .collect()
+    }
+}
+
+#[async_trait]
+impl AsyncTokenValidator for CompositeTokenValidator {
+    async fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let issuer = unverified_issuer(token);
+        let candidates = self.candidates(issuer.as_deref());
+
+        if candidates.is_empty() {
+            return Err(Error::new(
+                INVALID_TOKEN,
+                match issuer {
+                    Some(issuer) => format!("issuer '{}' is not accepted", issuer),
+                    None => "no validator accepts tokens without issuer".to_string(),
+                },
+            ));
+        }
+
+        let mut errors: Vec<(String, Error)> = Vec::new();
+
+        for candidate in candidates {
+            match candidate.token_validator.validate(token).await {
+                Ok(token) => {
+                    return Ok(Arc::new(ValidatedToken {
+                        token,
+                        validated_by: candidate.name.clone(),
+                    }))
+                }
+                Err(error) => errors.push((candidate.name.clone(), error)),
+            }
+        }
+
+        Err(aggregate_errors(errors))
+    }
+}
+
+/// `ValidatedToken` is a token accepted by one of the validators of `CompositeTokenValidator`.
+#[derive(Debug)]
+pub struct ValidatedToken {
+    token: Arc<dyn Token + Send + Sync>,
+    validated_by: String,
+}
+
+impl ValidatedToken {
+    pub fn inner(&self) -> &Arc<dyn Token + Send + Sync> {
+        &self.token
+    }
+}
+
+impl Token for ValidatedToken {
+    fn claim_value(&self, name: &str) -> Option<Value> {
+        self.token.claim_value(name)
+    }
+
+    fn subject(&self) -> Option<String> {
+        self.token.subject()
+    }
+
+    fn issuer(&self) -> Option<String> {
+        self.token.issuer()
+    }
+
+    fn audience(&self) -> Vec<String> {
+        self.token.audience()
+    }
+
+    fn expires_at(&self) -> Option<u64> {
+        self.token.expires_at()
+    }
+
+    fn issued_at(&self) -> Option<u64> {
+        self.token.issued_at()
+    }
+
+    fn token_id(&self) -> Option<String> {
+        self.token.token_id()
+    }
+
+    fn scopes(&self) -> Vec<String> {
+        self.token.scopes()
+    }
+
+    fn validated_by(&self) -> Option<String> {
+        Some(self.validated_by.clone())
+    }
+}
+
+fn same_issuer(accepted: &str, issuer: &str) -> bool {
+    accepted.trim_end_matches('/') == issuer.trim_end_matches('/')
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use jsonwebtoken::Algorithm;
+    use mockall::predicate::eq;
+
+    use crate::auth::async_token_validator::AsyncTokenValidator;
+    use crate::auth::composite_token_validator::CompositeTokenValidator;
+    use crate::auth::error_kind::{INTROSPECTION_FAILURE, INVALID_TOKEN, MALFORMED_TOKEN};
+    use crate::auth::jwt_issuer::{JwtIssuer, SigningKey};
+    use crate::auth::token::MockToken;
+    use crate::auth::token_validator::MockTokenValidator;
+    use crate::error::Error;
+
+    #[tokio::test]
+    pub async fn validate_routes_jwt_by_issuer() {
+        let first_issuer = get_issuer("https://first.example/");
+        let second_issuer = get_issuer("https://second.example/");
+        let token_validator = CompositeTokenValidator::default()
+            .with_jwt_validator("first", first_issuer.token_validator())
+            .with_jwt_validator("second", second_issuer.token_validator());
+
+        for (issuer, expected_validator) in [(first_issuer, "first"), (second_issuer, "second")] {
+            let token = issuer
+                .token()
+                .subject("user-1")
+                .sign()
+                .expect("expected signed token");
+
+            let validated_token = token_validator
+                .validate(token.as_str())
+                .await
+                .expect("expected valid token");
+
+            assert_eq!(
+                Some(expected_validator.to_string()),
+                validated_token.validated_by()
+            );
+            assert_eq!(Some("user-1".to_string()), validated_token.subject());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn validate_routed_jwt_keeps_validator_error() {
+        let issuer = get_issuer("https://first.example/");
+        let mut fallback = MockTokenValidator::new();
+        fallback.expect_validate().times(0);
+        let token_validator = CompositeTokenValidator::default()
+            .with_jwt_validator("first", issuer.token_validator())
+            .with_validator("fallback", Arc::new(fallback));
+        let token = issuer
+            .token()
+            .expired()
+            .sign()
+            .expect("expected signed token");
+
+        let error = token_validator
+            .validate(token.as_str())
+            .await
+            .expect_err("expected expired token");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+        assert!(error.message().contains("failed to validate token"));
+    }
+
+    #[tokio::test]
+    pub async fn validate_unknown_issuer_without_fallback_returns_invalid_token() {
+        let token_validator = CompositeTokenValidator::default().with_jwt_validator(
+            "first",
+            get_issuer("https://first.example/").token_validator(),
+        );
+        let token = get_issuer("https://unknown.example/")
+            .token()
+            .sign()
+            .expect("expected signed token");
+
+        let error = token_validator
+            .validate(token.as_str())
+            .await
+            .expect_err("expected unknown issuer");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+        assert!(error.message().contains("https://unknown.example/"));
+    }
+
+    #[tokio::test]
+    pub async fn validate_opaque_token_tries_fallback_validators_in_order() {
+        let mut first = MockTokenValidator::new();
+        first
+            .expect_validate()
+            .with(eq("opaque"))
+            .times(1)
+            .returning(|_| Err(Error::new(MALFORMED_TOKEN, "not an API key")));
+        let mut second = MockTokenValidator::new();
+        second
+            .expect_validate()
+            .with(eq("opaque"))
+            .times(1)
+            .returning(|_| Ok(Arc::new(MockToken::new())));
+        let token_validator = CompositeTokenValidator::default()
+            .with_jwt_validator(
+                "jwt",
+                get_issuer("https://first.example/").token_validator(),
+            )
+            .with_validator("api-key", Arc::new(first))
+            .with_validator("introspection", Arc::new(second));
+
+        let validated_token = token_validator
+            .validate("opaque")
+            .await
+            .expect("expected valid token");
+
+        assert_eq!(
+            Some("introspection".to_string()),
+            validated_token.validated_by()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn validate_aggregates_errors_of_every_validator() {
+        let token_validator = CompositeTokenValidator::default()
+            .with_validator(
+                "first",
+                Arc::new(get_failing_validator(INTROSPECTION_FAILURE)),
+            )
+            .with_validator(
+                "second",
+                Arc::new(get_failing_validator(INTROSPECTION_FAILURE)),
+            );
+        let mixed_token_validator = CompositeTokenValidator::default()
+            .with_validator("first", Arc::new(get_failing_validator(MALFORMED_TOKEN)))
+            .with_validator(
+                "second",
+                Arc::new(get_failing_validator(INTROSPECTION_FAILURE)),
+            );
+
+        let error = token_validator
+            .validate("opaque")
+            .await
+            .expect_err("expected every validator to fail");
+        let mixed_error = mixed_token_validator
+            .validate("opaque")
+            .await
+            .expect_err("expected every validator to fail");
+
+        assert_eq!(INTROSPECTION_FAILURE, error.error_kind());
+        assert_eq!(INTROSPECTION_FAILURE, mixed_error.error_kind());
+        assert!(mixed_error.message().contains("first: malformed_token"));
+        assert!(mixed_error
+            .message()
+            .contains("second: introspection_failure"));
+    }
+
+    fn get_failing_validator(error_kind: &'static str) -> MockTokenValidator {
+        let mut token_validator = MockTokenValidator::new();
+        token_validator
+            .expect_validate()
+            .returning(move |_| Err(Error::new(error_kind, "failed")));
+
+        token_validator
+    }
+
+    fn get_issuer(issuer: &str) -> JwtIssuer {
+        JwtIssuer::new(issuer)
+            .with_audience("cp-core")
+            .with_signing_key(
+                SigningKey::generate("es256", Algorithm::ES256).expect("expected signing key"),
+            )
+    }
+}
@@ -0,0 +1,242 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+
+use crate::auth::error_kind::{MALFORMED_CREDENTIALS, UNSUPPORTED_AUTHORIZATION_SCHEME};
+use crate::error::Error;
+use crate::{ok_or_return_error, some_or_return_error};
+
+pub const BEARER_SCHEME: &str = "Bearer";
+pub const BASIC_SCHEME: &str = "Basic";
+pub const DPOP_SCHEME: &str = "DPoP";
+pub const API_KEY_SCHEME: &str = "ApiKey";
+
+/// Credentials sent within the `Authorization` header of a request, or within a dedicated
+/// header in the case of API keys.
+#[derive(Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// Bearer token as described by RFC 6750.
+    Bearer(String),
+    /// User and password as described by RFC 7617.
+    Basic { username: String, password: String },
+    /// Proof-of-possession bound token as described by RFC 9449.
+    DPoP(String),
+    /// Static API key, never parsed from the `Authorization` header.
+    ApiKey(String),
+}
+
+impl Credentials {
+    ///
+    /// Parses the value of an `Authorization` header. Schemes are case-insensitive.
+    ///
+    /// # Arguments
+    ///
+    /// * `authorization` - Value of the header, i.e. `Bearer eyJhbGciOi...`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Parsed credentials.
+    /// * `Err` - Error with the kind `UNSUPPORTED_AUTHORIZATION_SCHEME` if the scheme is not
+    ///   `Bearer`, `Basic` or `DPoP`, or `MALFORMED_CREDENTIALS` if the credentials are not valid
+    ///   for the scheme.
+    pub fn parse(authorization: &str) -> Result<Self, Error> {
+        let authorization = authorization.trim();
+
+        let (scheme, value) = match authorization.split_once([' ', '\t']) {
+            Some((scheme, value)) => (scheme, value.trim()),
+            None => (authorization, ""),
+        };
+
+        if scheme.eq_ignore_ascii_case(BEARER_SCHEME) {
+            Ok(Self::Bearer(parse_token68(scheme, value)?))
+        } else if scheme.eq_ignore_ascii_case(DPOP_SCHEME) {
+            Ok(Self::DPoP(parse_token68(scheme, value)?))
+        } else if scheme.eq_ignore_ascii_case(BASIC_SCHEME) {
+            parse_basic(value)
+        } else {
+            Err(Error::new(
+                UNSUPPORTED_AUTHORIZATION_SCHEME,
+                format!("authorization scheme '{}' is not supported", scheme),
+            ))
+        }
+    }
+
+    /// Token of `Bearer`, `DPoP` and `ApiKey` credentials.
+    pub fn token(&self) -> Option<&str> {
+        match self {
+            Self::Bearer(token) | Self::DPoP(token) | Self::ApiKey(token) => Some(token.as_str()),
+            Self::Basic { .. } => None,
+        }
+    }
+
+    /// Name of the credentials' scheme.
+    pub fn scheme(&self) -> &'static str {
+        match self {
+            Self::Bearer(_) => BEARER_SCHEME,
+            Self::Basic { .. } => BASIC_SCHEME,
+            Self::DPoP(_) => DPOP_SCHEME,
+            Self::ApiKey(_) => API_KEY_SCHEME,
+        }
+    }
+}
+
+// credentials must not end up within logs
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Basic { username, .. } => f
+                .debug_struct("Basic")
+                .field("username", username)
+                .finish_non_exhaustive(),
+            credentials => f.write_str(credentials.scheme()),
+        }
+    }
+}
+
+fn parse_token68(scheme: &str, value: &str) -> Result<String, Error> {
+    if value.is_empty() {
+        return Err(Error::new(
+            MALFORMED_CREDENTIALS,
+            format!("'{}' credentials are missing the token", scheme),
+        ));
+    }
+
+    if !is_token68(value) {
+        return Err(Error::new(
+            MALFORMED_CREDENTIALS,
+            format!("'{}' token contains invalid characters", scheme),
+        ));
+    }
+
+    Ok(value.to_string())
+}
+
+fn parse_basic(value: &str) -> Result<Credentials, Error> {
+    if value.is_empty() || !is_token68(value) {
+        return Err(Error::new(
+            MALFORMED_CREDENTIALS,
+            "'Basic' credentials are not valid base64",
+        ));
+    }
+
+    let decoded = ok_or_return_error!(
+        STANDARD.decode(value),
+        MALFORMED_CREDENTIALS,
+        "'Basic' credentials are not valid base64: "
+    );
+
+    let decoded = ok_or_return_error!(
+        String::from_utf8(decoded),
+        MALFORMED_CREDENTIALS,
+        "'Basic' credentials are not valid UTF-8: "
+    );
+
+    let (username, password) = some_or_return_error!(
+        decoded.split_once(':'),
+        MALFORMED_CREDENTIALS,
+        "'Basic' credentials are missing the ':' separator"
+    );
+
+    Ok(Credentials::Basic {
+        username: username.to_string(),
+        password: password.to_string(),
+    })
+}
+
+// token68 = 1*( ALPHA / DIGIT / "-" / "." / "_" / "~" / "+" / "/" ) *"="
+fn is_token68(value: &str) -> bool {
+    let content = value.trim_end_matches('=');
+
+    !content.is_empty()
+        && content
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '_' | '~' | '+' | '/'))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::auth::credentials::Credentials;
+    use crate::auth::error_kind::{MALFORMED_CREDENTIALS, UNSUPPORTED_AUTHORIZATION_SCHEME};
+
+    #[test]
+    pub fn parse_bearer_is_case_insensitive() {
+        for authorization in [
+            "Bearer abc.def-ghi",
+            "bearer abc.def-ghi",
+            "BEARER  abc.def-ghi",
+        ] {
+            assert_eq!(
+                Credentials::Bearer("abc.def-ghi".to_string()),
+                Credentials::parse(authorization).expect("expected bearer credentials")
+            );
+        }
+    }
+
+    #[test]
+    pub fn parse_dpop_returns_token() {
+        let credentials = Credentials::parse("DPoP abc123").expect("expected DPoP credentials");
+
+        assert_eq!(Credentials::DPoP("abc123".to_string()), credentials);
+        assert_eq!(Some("abc123"), credentials.token());
+    }
+
+    #[test]
+    pub fn parse_basic_decodes_username_and_password() {
+        // base64 of 'client:se:cret'
+        let credentials =
+            Credentials::parse("Basic Y2xpZW50OnNlOmNyZXQ=").expect("expected basic credentials");
+
+        assert_eq!(
+            Credentials::Basic {
+                username: "client".to_string(),
+                password: "se:cret".to_string()
+            },
+            credentials
+        );
+        assert_eq!(None, credentials.token());
+    }
+
+    #[test]
+    pub fn parse_malformed_credentials_returns_error() {
+        for authorization in [
+            "Bearer",
+            "Bearer ",
+            "Bearer abc def",
+            "Bearer ===",
+            "Basic",
+            "Basic bm9zZXBhcmF0b3I=",
+            "Basic %%%",
+        ] {
+            let error = Credentials::parse(authorization)
+                .expect_err(format!("expected '{}' to be malformed", authorization).as_str());
+
+            assert_eq!(MALFORMED_CREDENTIALS, error.error_kind());
+        }
+    }
+
+    #[test]
+    pub fn parse_unknown_scheme_returns_error() {
+        let error =
+            Credentials::parse("Digest username=\"user\"").expect_err("expected unknown scheme");
+
+        assert_eq!(UNSUPPORTED_AUTHORIZATION_SCHEME, error.error_kind());
+    }
+
+    #[test]
+    pub fn debug_does_not_leak_secrets() {
+        let bearer = format!("{:?}", Credentials::Bearer("secret-token".to_string()));
+        let basic = format!(
+            "{:?}",
+            Credentials::Basic {
+                username: "client".to_string(),
+                password: "secret".to_string()
+            }
+        );
+
+        assert!(!bearer.contains("secret"));
+        assert!(!basic.contains("secret"));
+    }
+}
@@ -0,0 +1,806 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use axum::http::header::HOST;
+use axum::http::request::Parts;
+use axum::http::Uri;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{decode, decode_header, get_current_timestamp, Algorithm, Validation};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::auth::error_kind::{INVALID_DPOP_PROOF, INVALID_TOKEN};
+use crate::auth::jwt_token_validator::decoding_key_for_jwk;
+use crate::auth::token::{Token, CONFIRMATION_CLAIM};
+use crate::error::Error;
+use crate::{ok_or_return_error, some_or_return_error};
+
+pub const DPOP_HEADER: &str = "DPoP";
+pub const DPOP_TOKEN_TYPE: &str = "dpop+jwt";
+pub const JWK_THUMBPRINT_CONFIRMATION: &str = "jkt";
+
+const DEFAULT_MAX_AGE_IN_SECONDS: u64 = 300;
+const DEFAULT_LEEWAY_IN_SECONDS: u64 = 60;
+
+const DEFAULT_ALGORITHMS: [Algorithm; 9] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+    Algorithm::ES256,
+    Algorithm::ES384,
+    Algorithm::EdDSA,
+];
+
+/// Proof validated by `DpopValidator`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DpopProof {
+    thumbprint: String,
+    token_id: String,
+    issued_at: u64,
+    expires_at: u64,
+}
+
+impl DpopProof {
+    /// JWK SHA-256 thumbprint (RFC 7638) of the key which signed the proof.
+    pub fn thumbprint(&self) -> &str {
+        self.thumbprint.as_str()
+    }
+
+    pub fn token_id(&self) -> &str {
+        self.token_id.as_str()
+    }
+
+    pub fn issued_at(&self) -> u64 {
+        self.issued_at
+    }
+}
+
+/// `DpopValidator` validates DPoP proofs as described by RFC 9449: the proof must be signed by
+/// the key embedded within its header, target the method and URI of the request, be recent,
+/// and be used only once.
+///
+/// Used `jti` values are kept in memory until the proof would be too old anyway, so a replay
+/// is only detected within the same process.
+///
+/// Without a public URL, the expected `htu` of requests whose URI is a path only is built from
+/// the `Host` header, which the client controls. This is only safe if the service is reached
+/// over HTTPS and the `Host` header is validated by a reverse proxy, so a public URL should be
+/// configured whenever possible.
+pub struct DpopValidator {
+    max_age: Duration,
+    leeway: Duration,
+    algorithms: Vec<Algorithm>,
+    public_url: Option<String>,
+    used_token_ids: Mutex<HashMap<String, u64>>,
+}
+
+impl Default for DpopValidator {
+    fn default() -> Self {
+        Self {
+            max_age: Duration::from_secs(DEFAULT_MAX_AGE_IN_SECONDS),
+            leeway: Duration::from_secs(DEFAULT_LEEWAY_IN_SECONDS),
+            algorithms: DEFAULT_ALGORITHMS.to_vec(),
+            public_url: None,
+            used_token_ids: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl DpopValidator {
+    /// Rejects proofs whose `iat` claim is older than `max_age`.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    /// Clock skew tolerated for proofs issued in the future.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Asymmetric algorithms accepted for the proof signature.
+    pub fn with_algorithms(mut self, algorithms: Vec<Algorithm>) -> Self {
+        self.algorithms = algorithms;
+        self
+    }
+
+    /// URL under which clients reach the service, i.e. `https://api.example.com` when behind a
+    /// reverse proxy. Used for building the expected `htu` of requests whose URI is a path only.
+    /// If missing, `https://` and the client-controlled `Host` header are used, trusting that
+    /// the `Host` header has been validated before reaching the service.
+    pub fn with_public_url(mut self, public_url: impl Into<String>) -> Self {
+        self.public_url = Some(public_url.into().trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Algorithms accepted for the proof signature, i.e. for the `algs` challenge parameter.
+    pub fn algorithms(&self) -> &[Algorithm] {
+        self.algorithms.as_slice()
+    }
+
+    ///
+    /// Validates the `DPoP` header of the request and registers its `jti` as used.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - Head of the request.
+    /// * `access_token` - Access token sent along with the proof.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validated proof.
+    /// * `Err` - Error with the kind `INVALID_DPOP_PROOF` if the header is missing, repeated
+    ///   or the proof is not valid for the request.
+    pub fn validate_request(&self, parts: &Parts, access_token: &str) -> Result<DpopProof, Error> {
+        let proof = self.verify_request(parts, access_token)?;
+        self.register(&proof)?;
+
+        Ok(proof)
+    }
+
+    ///
+    /// Validates the `DPoP` header of the request without registering its `jti`, so that it can
+    /// be registered with `register` once the access token has been validated as well.
+    ///
+    /// # Arguments
+    ///
+    /// * `parts` - Head of the request.
+    /// * `access_token` - Access token sent along with the proof.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validated proof, not yet registered as used.
+    /// * `Err` - Error with the kind `INVALID_DPOP_PROOF` if the header is missing, repeated
+    ///   or the proof is not valid for the request.
+    pub fn verify_request(&self, parts: &Parts, access_token: &str) -> Result<DpopProof, Error> {
+        let mut proofs = parts.headers.get_all(DPOP_HEADER).iter();
+
+        let proof = some_or_return_error!(
+            proofs.next(),
+            INVALID_DPOP_PROOF,
+            "'DPoP' header is missing"
+        );
+
+        if proofs.next().is_some() {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                "request contains more than one 'DPoP' header",
+            ));
+        }
+
+        let proof = ok_or_return_error!(
+            proof.to_str(),
+            INVALID_DPOP_PROOF,
+            "could not read 'DPoP' header as string: "
+        );
+
+        let htu = self.request_htu(parts)?;
+
+        self.verify(
+            proof,
+            parts.method.as_str(),
+            htu.as_str(),
+            Some(access_token),
+        )
+    }
+
+    ///
+    /// Validates a DPoP proof and registers its `jti` as used.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - DPoP proof JWT.
+    /// * `method` - HTTP method of the request, compared against `htm`.
+    /// * `htu` - URI of the request, compared against `htu` ignoring the query and fragment.
+    /// * `access_token` - Access token sent along with the proof, compared against `ath`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validated proof.
+    /// * `Err` - Error with the kind `INVALID_DPOP_PROOF` describing the failed check.
+    pub fn validate(
+        &self,
+        proof: &str,
+        method: &str,
+        htu: &str,
+        access_token: Option<&str>,
+    ) -> Result<DpopProof, Error> {
+        let proof = self.verify(proof, method, htu, access_token)?;
+        self.register(&proof)?;
+
+        Ok(proof)
+    }
+
+    ///
+    /// Validates a DPoP proof without registering its `jti`.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - DPoP proof JWT.
+    /// * `method` - HTTP method of the request, compared against `htm`.
+    /// * `htu` - URI of the request, compared against `htu` ignoring the query and fragment.
+    /// * `access_token` - Access token sent along with the proof, compared against `ath`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validated proof, not yet registered as used.
+    /// * `Err` - Error with the kind `INVALID_DPOP_PROOF` describing the failed check.
+    pub fn verify(
+        &self,
+        proof: &str,
+        method: &str,
+        htu: &str,
+        access_token: Option<&str>,
+    ) -> Result<DpopProof, Error> {
+        let header = ok_or_return_error!(
+            decode_header(proof),
+            INVALID_DPOP_PROOF,
+            "failed to decode the proof header: "
+        );
+
+        if header.typ.as_deref() != Some(DPOP_TOKEN_TYPE) {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                format!("proof 'typ' must be '{}'", DPOP_TOKEN_TYPE),
+            ));
+        }
+
+        if !self.algorithms.contains(&header.alg) {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                format!("proof algorithm '{:?}' is not accepted", header.alg),
+            ));
+        }
+
+        let jwk = some_or_return_error!(
+            header.jwk,
+            INVALID_DPOP_PROOF,
+            "proof header is missing the 'jwk'"
+        );
+
+        let thumbprint = jwk_thumbprint(&raw_header_jwk(proof)?)?;
+
+        let (decoding_key, algorithm) = match decoding_key_for_jwk(&jwk, header.alg) {
+            Ok(key) => key,
+            Err(error) => return Err(Error::new(INVALID_DPOP_PROOF, error.message())),
+        };
+
+        if algorithm != header.alg {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                "proof algorithm does not match the 'jwk'",
+            ));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+
+        let claims = ok_or_return_error!(
+            decode::<HashMap<String, Value>>(proof, &decoding_key, &validation),
+            INVALID_DPOP_PROOF,
+            "failed to validate the proof: "
+        )
+        .claims;
+
+        let proof_method = string_claim(&claims, "htm")?;
+        if proof_method != method {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                "proof 'htm' does not match the request method",
+            ));
+        }
+
+        let proof_uri = string_claim(&claims, "htu")?;
+        let request_uri = normalize_htu(htu);
+        if request_uri.is_none() || normalize_htu(proof_uri.as_str()) != request_uri {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                "proof 'htu' does not match the request URI",
+            ));
+        }
+
+        if let Some(access_token) = access_token {
+            let expected_hash = URL_SAFE_NO_PAD.encode(Sha256::digest(access_token.as_bytes()));
+
+            if string_claim(&claims, "ath")? != expected_hash {
+                return Err(Error::new(
+                    INVALID_DPOP_PROOF,
+                    "proof 'ath' does not match the access token",
+                ));
+            }
+        }
+
+        let issued_at = some_or_return_error!(
+            claims.get("iat").and_then(|iat| iat.as_u64()),
+            INVALID_DPOP_PROOF,
+            "proof is missing the 'iat' claim"
+        );
+        let now = get_current_timestamp();
+
+        if issued_at > now + self.leeway.as_secs() {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                "proof has been issued in the future",
+            ));
+        }
+
+        if issued_at + self.max_age.as_secs() < now {
+            return Err(Error::new(INVALID_DPOP_PROOF, "proof is too old"));
+        }
+
+        let token_id = string_claim(&claims, "jti")?;
+
+        Ok(DpopProof {
+            thumbprint,
+            token_id,
+            issued_at,
+            expires_at: issued_at + self.max_age.as_secs() + self.leeway.as_secs(),
+        })
+    }
+
+    ///
+    /// Registers the `jti` of a verified proof as used, rejecting replays.
+    ///
+    /// # Arguments
+    ///
+    /// * `proof` - Proof verified by `verify` or `verify_request`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - If the proof has not been used before.
+    /// * `Err` - Error with the kind `INVALID_DPOP_PROOF` if the proof has already been used.
+    pub fn register(&self, proof: &DpopProof) -> Result<(), Error> {
+        self.register_token_id(proof.token_id.as_str(), proof.expires_at)
+    }
+
+    fn request_htu(&self, parts: &Parts) -> Result<String, Error> {
+        if parts.uri.scheme().is_some() && parts.uri.authority().is_some() {
+            return Ok(parts.uri.to_string());
+        }
+
+        let path = parts.uri.path();
+
+        if let Some(ref public_url) = self.public_url {
+            return Ok(format!("{}{}", public_url, path));
+        }
+
+        let host = some_or_return_error!(
+            parts.headers.get(HOST).and_then(|host| host.to_str().ok()),
+            INVALID_DPOP_PROOF,
+            "could not determine the request URI for the 'htu' check"
+        );
+
+        Ok(format!("https://{}{}", host, path))
+    }
+
+    fn register_token_id(&self, token_id: &str, expires_at: u64) -> Result<(), Error> {
+        let mut used_token_ids = self
+            .used_token_ids
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let now = get_current_timestamp();
+
+        used_token_ids.retain(|_, expires_at| *expires_at >= now);
+
+        if used_token_ids.contains_key(token_id) {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                "proof has already been used",
+            ));
+        }
+
+        used_token_ids.insert(token_id.to_string(), expires_at);
+
+        Ok(())
+    }
+}
+
+///
+/// Checks the token is bound to the key of the proof through its `cnf.jkt` claim.
+///
+/// # Arguments
+///
+/// * `token` - Validated access token.
+/// * `proof` - Proof validated for the same request.
+///
+/// # Returns
+///
+/// * `Ok` - If the token is bound to the proof key.
+/// * `Err` - Error with the kind `INVALID_TOKEN` otherwise.
+pub fn verify_binding(token: &(dyn Token + Send + Sync), proof: &DpopProof) -> Result<(), Error> {
+    match jwk_thumbprint_confirmation(token) {
+        Some(thumbprint) if thumbprint == proof.thumbprint => Ok(()),
+        Some(_) => Err(Error::new(
+            INVALID_TOKEN,
+            "token is bound to a different key than the DPoP proof",
+        )),
+        None => Err(Error::new(
+            INVALID_TOKEN,
+            "token is not bound to a DPoP key",
+        )),
+    }
+}
+
+/// Gets the `cnf.jkt` claim of the token, present on DPoP-bound tokens.
+pub fn jwk_thumbprint_confirmation(token: &(dyn Token + Send + Sync)) -> Option<String> {
+    match token.claim_value(CONFIRMATION_CLAIM) {
+        Some(Value::Object(confirmation)) => confirmation
+            .get(JWK_THUMBPRINT_CONFIRMATION)
+            .and_then(|thumbprint| thumbprint.as_str())
+            .map(String::from),
+        _ => None,
+    }
+}
+
+///
+/// Computes the JWK SHA-256 thumbprint as described by RFC 7638.
+///
+/// # Arguments
+///
+/// * `jwk` - Public key as JSON.
+///
+/// # Returns
+///
+/// * `Ok` - Base64url encoded thumbprint.
+/// * `Err` - Error with the kind `INVALID_DPOP_PROOF` if the key type is not supported or a
+///   required member is missing.
+pub fn jwk_thumbprint(jwk: &Value) -> Result<String, Error> {
+    let key_type = some_or_return_error!(
+        jwk.get("kty").and_then(|key_type| key_type.as_str()),
+        INVALID_DPOP_PROOF,
+        "'jwk' is missing the 'kty'"
+    );
+
+    let required_members: &[&str] = match key_type {
+        "EC" => &["crv", "kty", "x", "y"],
+        "RSA" => &["e", "kty", "n"],
+        "OKP" => &["crv", "kty", "x"],
+        _ => {
+            return Err(Error::new(
+                INVALID_DPOP_PROOF,
+                format!("'jwk' type '{}' is not supported", key_type),
+            ))
+        }
+    };
+
+    let mut members: BTreeMap<&str, &str> = BTreeMap::new();
+
+    for member in required_members {
+        let value = some_or_return_error!(
+            jwk.get(*member).and_then(|value| value.as_str()),
+            INVALID_DPOP_PROOF,
+            format!("'jwk' is missing the '{}'", member)
+        );
+
+        members.insert(member, value);
+    }
+
+    let canonical_jwk = ok_or_return_error!(
+        serde_json::to_string(&members),
+        INVALID_DPOP_PROOF,
+        "failed to serialize the 'jwk': "
+    );
+
+    Ok(URL_SAFE_NO_PAD.encode(Sha256::digest(canonical_jwk.as_bytes())))
+}
+
+// the header is decoded once more since `Jwk` silently drops private members
+fn raw_header_jwk(proof: &str) -> Result<Value, Error> {
+    let encoded_header = proof.split('.').next().unwrap_or_default();
+
+    let header = ok_or_return_error!(
+        URL_SAFE_NO_PAD.decode(encoded_header),
+        INVALID_DPOP_PROOF,
+        "failed to decode the proof header: "
+    );
+
+    let mut header = ok_or_return_error!(
+        serde_json::from_slice::<HashMap<String, Value>>(header.as_slice()),
+        INVALID_DPOP_PROOF,
+        "failed to deserialize the proof header: "
+    );
+
+    let jwk = some_or_return_error!(
+        header.remove("jwk"),
+        INVALID_DPOP_PROOF,
+        "proof header is missing the 'jwk'"
+    );
+
+    if jwk.get("d").is_some() {
+        return Err(Error::new(
+            INVALID_DPOP_PROOF,
+            "proof 'jwk' must not contain a private key",
+        ));
+    }
+
+    Ok(jwk)
+}
+
+fn string_claim(claims: &HashMap<String, Value>, name: &str) -> Result<String, Error> {
+    match claims.get(name) {
+        Some(Value::String(value)) if !value.is_empty() => Ok(value.clone()),
+        _ => Err(Error::new(
+            INVALID_DPOP_PROOF,
+            format!("proof is missing the '{}' claim", name),
+        )),
+    }
+}
+
+// syntax-based normalization of RFC 3986, without the query and fragment
+fn normalize_htu(htu: &str) -> Option<String> {
+    let uri = htu.parse::<Uri>().ok()?;
+    let scheme = uri.scheme_str()?.to_ascii_lowercase();
+    let authority = uri.authority()?;
+    let host = authority.host().to_ascii_lowercase();
+    let port = match (scheme.as_str(), authority.port_u16()) {
+        ("https", Some(443)) | ("http", Some(80)) | (_, None) => String::new(),
+        (_, Some(port)) => format!(":{}", port),
+    };
+    let path = match uri.path() {
+        "" => "/",
+        path => path,
+    };
+
+    Some(format!("{}://{}{}{}", scheme, host, port, path))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use axum::extract::Request;
+    use axum::http::request::Parts;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::jwk::Jwk;
+    use jsonwebtoken::{encode, get_current_timestamp, Algorithm, EncodingKey, Header};
+    use serde_json::{json, Value};
+    use sha2::{Digest, Sha256};
+
+    use crate::auth::dpop::{jwk_thumbprint, verify_binding, DpopValidator, DPOP_HEADER};
+    use crate::auth::error_kind::{INVALID_DPOP_PROOF, INVALID_TOKEN};
+    use crate::test_base::{get_jwt_token, get_unit_test_data_path};
+
+    pub const HTU: &str = "https://api.example.com/resources";
+    pub const ACCESS_TOKEN: &str = "access-token";
+
+    #[test]
+    pub fn jwk_thumbprint_matches_rfc_7638_example() {
+        let jwk = json!({
+            "kty": "RSA",
+            "n": "0vx7agoebGcQSuuPiLJXZptN9nndrQmbXEps2aiAFbWhM78LhWx4cbbfAAtVT86zwu1RK7aPFFxuhDR1L6tSoc_BJECPebWKRXjBZCiFV4n3oknjhMstn64tZ_2W-5JsGY4Hc5n9yBXArwl93lqt7_RN5w6Cf0h4QyQ5v-65YGjQR0_FDW2QvzqY368QQMicAtaSqzs8KJZgnYb9c7d0zgdAZHzu6qMQvRL5hajrn1n91CbOpbISD08qNLyrdkt-bFTWhAI4vMQFh6WeZu0fM4lFd2NcRwr3XPksINHaQ-G_xBniIqbw0Ls1jF44-csFCur-kEgU8awapJzKnqDKgw",
+            "e": "AQAB",
+            "alg": "RS256",
+            "kid": "2011-04-29"
+        });
+
+        let thumbprint = jwk_thumbprint(&jwk).expect("expected thumbprint");
+
+        assert_eq!("NzbLsXh8uDCcd-6MNwXF4W_7noWXFZAfHkxZsRGC9Xs", thumbprint);
+    }
+
+    #[test]
+    pub fn validate_valid_proof_returns_key_thumbprint() {
+        let validator = DpopValidator::default();
+        let proof = encode_proof(get_proof_claims());
+
+        let dpop_proof = validator
+            .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect("expected valid proof");
+
+        assert_eq!(
+            jwk_thumbprint(&get_public_jwk()).expect("expected thumbprint"),
+            dpop_proof.thumbprint()
+        );
+    }
+
+    #[test]
+    pub fn validate_ignores_query_and_normalizes_htu() {
+        let validator = DpopValidator::default();
+        let mut claims = get_proof_claims();
+        claims["htu"] = json!("HTTPS://API.example.com:443/resources");
+        let proof = encode_proof(claims);
+
+        let result = validator.validate(
+            proof.as_str(),
+            "GET",
+            "https://api.example.com/resources?page=2",
+            Some(ACCESS_TOKEN),
+        );
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    pub fn validate_mismatching_proof_returns_error() {
+        let validator = DpopValidator::default();
+        let now = get_current_timestamp();
+        let cases = [
+            ("htm", json!("POST")),
+            ("htu", json!("https://api.example.com/other")),
+            ("ath", json!("not-the-hash")),
+            ("iat", json!(now - 3600)),
+            ("iat", json!(now + 3600)),
+            ("jti", json!("")),
+        ];
+
+        for (claim, value) in cases {
+            let mut claims = get_proof_claims();
+            claims[claim] = value;
+            let proof = encode_proof(claims);
+
+            let error = validator
+                .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+                .expect_err(format!("expected invalid '{}'", claim).as_str());
+
+            assert_eq!(INVALID_DPOP_PROOF, error.error_kind());
+        }
+    }
+
+    #[test]
+    pub fn validate_replayed_proof_returns_error() {
+        let validator = DpopValidator::default();
+        let proof = encode_proof(get_proof_claims());
+
+        validator
+            .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect("expected valid proof");
+        let error = validator
+            .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect_err("expected replayed proof");
+
+        assert_eq!(INVALID_DPOP_PROOF, error.error_kind());
+        assert!(error.message().contains("already been used"));
+    }
+
+    #[test]
+    pub fn verify_does_not_register_proof_until_registered() {
+        let validator = DpopValidator::default();
+        let proof = encode_proof(get_proof_claims());
+
+        let dpop_proof = validator
+            .verify(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect("expected valid proof");
+        validator
+            .verify(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect("expected proof not to be registered");
+        validator
+            .register(&dpop_proof)
+            .expect("expected proof to be registered");
+        let error = validator
+            .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect_err("expected replayed proof");
+
+        assert_eq!(INVALID_DPOP_PROOF, error.error_kind());
+    }
+
+    #[test]
+    pub fn validate_proof_with_wrong_type_or_foreign_key_returns_error() {
+        let validator = DpopValidator::default();
+        let mut header = get_proof_header();
+        header.typ = Some("JWT".to_string());
+        let wrong_type = encode_proof_with_header(header, get_proof_claims());
+        let mut jwk = get_public_jwk();
+        jwk["x"] = json!("FC1NAHV9ofBa-xc5hOL2MkLSNDE121ip0xSrY2WwskeiX0nD-Cj5vTUNKlg121qO");
+        let foreign_key = encode_proof_with_header(
+            Header {
+                jwk: Some(serde_json::from_value::<Jwk>(jwk).expect("expected 'Jwk'")),
+                ..get_proof_header()
+            },
+            get_proof_claims(),
+        );
+
+        for proof in [wrong_type, foreign_key] {
+            let error = validator
+                .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+                .expect_err("expected invalid proof");
+
+            assert_eq!(INVALID_DPOP_PROOF, error.error_kind());
+        }
+    }
+
+    #[test]
+    pub fn validate_request_builds_htu_from_host_header() {
+        let validator = DpopValidator::default();
+        let proof = encode_proof(get_proof_claims());
+        let parts = get_parts("/resources?page=2", Some(proof.as_str()));
+
+        let result = validator.validate_request(&parts, ACCESS_TOKEN);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    pub fn validate_request_without_proof_returns_error() {
+        let validator = DpopValidator::default();
+        let parts = get_parts("/resources", None);
+
+        let error = validator
+            .validate_request(&parts, ACCESS_TOKEN)
+            .expect_err("expected missing proof");
+
+        assert_eq!(INVALID_DPOP_PROOF, error.error_kind());
+    }
+
+    #[test]
+    pub fn verify_binding_checks_jwk_thumbprint_confirmation() {
+        let validator = DpopValidator::default();
+        let proof = encode_proof(get_proof_claims());
+        let dpop_proof = validator
+            .validate(proof.as_str(), "GET", HTU, Some(ACCESS_TOKEN))
+            .expect("expected valid proof");
+        let bound_token = get_jwt_token(json!({ "cnf": { "jkt": dpop_proof.thumbprint() } }));
+        let other_token = get_jwt_token(json!({ "cnf": { "jkt": "other" } }));
+        let unbound_token = get_jwt_token(json!({}));
+
+        assert!(verify_binding(&bound_token, &dpop_proof).is_ok());
+        for token in [other_token, unbound_token] {
+            let error = verify_binding(&token, &dpop_proof).expect_err("expected unbound token");
+
+            assert_eq!(INVALID_TOKEN, error.error_kind());
+        }
+    }
+
+    pub fn encode_proof(claims: Value) -> String {
+        encode_proof_with_header(get_proof_header(), claims)
+    }
+
+    pub fn get_proof_claims() -> Value {
+        json!({
+            "jti": uuid::Uuid::new_v4().to_string(),
+            "htm": "GET",
+            "htu": HTU,
+            "iat": get_current_timestamp(),
+            "ath": URL_SAFE_NO_PAD.encode(Sha256::digest(ACCESS_TOKEN.as_bytes())),
+        })
+    }
+
+    pub fn get_public_jwk() -> Value {
+        serde_json::from_slice(read_test_file("jwk.json").as_slice()).expect("expected 'jwk'")
+    }
+
+    fn encode_proof_with_header(header: Header, claims: Value) -> String {
+        let encoding_key = EncodingKey::from_ec_pem(read_test_file("private_key.pem").as_slice())
+            .expect("expected encoding key");
+
+        encode(&header, &claims, &encoding_key).expect("expected proof")
+    }
+
+    fn get_proof_header() -> Header {
+        Header {
+            typ: Some("dpop+jwt".to_string()),
+            jwk: Some(serde_json::from_value::<Jwk>(get_public_jwk()).expect("expected 'Jwk'")),
+            ..Header::new(Algorithm::ES256)
+        }
+    }
+
+    fn get_parts(uri: &str, proof: Option<&str>) -> Parts {
+        let mut builder = Request::builder()
+            .uri(uri)
+            .header("Host", "api.example.com");
+
+        if let Some(proof) = proof {
+            builder = builder.header(DPOP_HEADER, proof);
+        }
+
+        let (parts, _) = builder.body(()).expect("expected request").into_parts();
+
+        parts
+    }
+
+    fn read_test_file(file_name: &str) -> Vec<u8> {
+        let mut path = get_unit_test_data_path(file!());
+        path.push(file_name);
+
+        std::fs::read(path).expect("expected test file")
+    }
+}
@@ -6,3 +6,16 @@ pub const JWKS_RETRIEVAL_FAILURE: &str = "jwks_retrieval_failure";
 pub const MALFORMED_TOKEN: &str = "malformed_token";
 pub const INVALID_HEADERS: &str = "invalid_headers";
 pub const INVALID_TOKEN: &str = "invalid_token";
+pub const FORBIDDEN: &str = "forbidden";
+pub const INVALID_ALGORITHM: &str = "invalid_algorithm";
+pub const DISCOVERY_FAILURE: &str = "discovery_failure";
+pub const INTROSPECTION_FAILURE: &str = "introspection_failure";
+pub const REVOKED_TOKEN: &str = "revoked_token";
+pub const MISSING_CREDENTIALS: &str = "missing_credentials";
+pub const MALFORMED_CREDENTIALS: &str = "malformed_credentials";
+pub const MULTIPLE_CREDENTIALS: &str = "multiple_credentials";
+pub const UNSUPPORTED_AUTHORIZATION_SCHEME: &str = "unsupported_authorization_scheme";
+pub const INVALID_DPOP_PROOF: &str = "invalid_dpop_proof";
+pub const INVALID_KEY: &str = "invalid_key";
+pub const SIGNING_FAILURE: &str = "signing_failure";
+pub const MISSING_AUTHENTICATION_LAYER: &str = "missing_authentication_layer";
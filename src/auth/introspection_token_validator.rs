@@ -0,0 +1,338 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use async_trait::async_trait;
+use jsonwebtoken::get_current_timestamp;
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::auth::async_token_validator::AsyncTokenValidator;
+use crate::auth::claims_token::ClaimsToken;
+use crate::auth::error_kind::{INTROSPECTION_FAILURE, INVALID_TOKEN};
+use crate::auth::token::Token;
+use crate::error::Error;
+use crate::ok_or_return_error;
+use crate::secrets::secrets_manager::SecretsManager;
+
+pub const ACTIVE_CLAIM: &str = "active";
+
+const DEFAULT_MAX_CACHED_TOKENS: usize = 10000;
+
+/// `IntrospectionTokenValidator` validates opaque tokens by asking the authorization server
+/// through its OAuth 2.0 token introspection endpoint (RFC 7662).
+///
+/// Active tokens are cached until their `exp` claim, so repeated requests with the same
+/// token do not reach the authorization server.
+pub struct IntrospectionTokenValidator {
+    introspection_endpoint: String,
+    client_id: String,
+    client_secret: String,
+    client: Client,
+    max_cached_tokens: usize,
+    cache: Mutex<HashMap<Vec<u8>, Arc<ClaimsToken>>>,
+}
+
+impl IntrospectionTokenValidator {
+    pub fn new(
+        introspection_endpoint: String,
+        client_id: String,
+        client_secret: String,
+        client: Client,
+    ) -> Self {
+        Self {
+            introspection_endpoint,
+            client_id,
+            client_secret,
+            client,
+            max_cached_tokens: DEFAULT_MAX_CACHED_TOKENS,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    ///
+    /// Creates a validator whose client secret is stored within the secrets manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `introspection_endpoint` - URL of the introspection endpoint.
+    /// * `client_id` - Client ID used for authenticating against the endpoint.
+    /// * `secrets_manager` - Secrets manager holding the client secret.
+    /// * `client_secret_id` - ID of the client secret within the secrets manager.
+    /// * `client` - HTTP client used for the requests.
+    pub fn from_secrets_manager(
+        introspection_endpoint: String,
+        client_id: String,
+        secrets_manager: &dyn SecretsManager,
+        client_secret_id: &str,
+        client: Client,
+    ) -> Result<Self, Error> {
+        let client_secret = secrets_manager.get_secret(client_secret_id)?;
+
+        Ok(Self::new(
+            introspection_endpoint,
+            client_id,
+            client_secret,
+            client,
+        ))
+    }
+
+    /// Maximum amount of active tokens kept in cache.
+    pub fn with_max_cached_tokens(mut self, max_cached_tokens: usize) -> Self {
+        self.max_cached_tokens = max_cached_tokens;
+        self
+    }
+
+    ///
+    /// Introspects the specified token, unless an active result is cached for it.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Opaque token to be introspected.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Token holding the claims returned by the introspection endpoint.
+    /// * `Err` - Error with the kind `INVALID_TOKEN` if the token is not active, or
+    ///   `INTROSPECTION_FAILURE` if the endpoint could not be reached.
+    pub async fn introspect(&self, token: &str) -> Result<Arc<ClaimsToken>, Error> {
+        let key = cache_key(token);
+
+        if let Some(cached_token) = self.cached(&key) {
+            return Ok(cached_token);
+        }
+
+        let response = ok_or_return_error!(
+            self.client
+                .post(self.introspection_endpoint.as_str())
+                .basic_auth(
+                    form_urlencode(&self.client_id),
+                    Some(form_urlencode(&self.client_secret))
+                )
+                .form(&[("token", token), ("token_type_hint", "access_token")])
+                .send()
+                .await,
+            INTROSPECTION_FAILURE,
+            "failed to request the token introspection: "
+        );
+
+        let response = ok_or_return_error!(
+            response.error_for_status(),
+            INTROSPECTION_FAILURE,
+            "invalid response obtained introspecting the token: "
+        );
+
+        let claims = ok_or_return_error!(
+            response.json::<HashMap<String, Value>>().await,
+            INTROSPECTION_FAILURE,
+            "response cannot be deserialized as an introspection response: "
+        );
+
+        let is_active = claims
+            .get(ACTIVE_CLAIM)
+            .and_then(|active| active.as_bool())
+            .unwrap_or(false);
+
+        if !is_active {
+            return Err(Error::new(INVALID_TOKEN, "token is not active"));
+        }
+
+        let introspected_token = Arc::new(ClaimsToken::new(claims));
+
+        if let Some(expires_at) = introspected_token.expires_at() {
+            if expires_at <= get_current_timestamp() {
+                return Err(Error::new(INVALID_TOKEN, "token has expired"));
+            }
+
+            self.insert_cache(key, introspected_token.clone());
+        }
+
+        Ok(introspected_token)
+    }
+
+    fn cached(&self, key: &[u8]) -> Option<Arc<ClaimsToken>> {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+
+        match cache.get(key) {
+            Some(token) if !is_expired(token.as_ref()) => Some(token.clone()),
+            Some(_) => {
+                cache.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn insert_cache(&self, key: Vec<u8>, token: Arc<ClaimsToken>) {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if cache.len() >= self.max_cached_tokens {
+            cache.retain(|_, token| !is_expired(token.as_ref()));
+        }
+
+        if cache.len() < self.max_cached_tokens {
+            cache.insert(key, token);
+        }
+    }
+}
+
+#[async_trait]
+impl AsyncTokenValidator for IntrospectionTokenValidator {
+    async fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let introspected_token = self.introspect(token).await?;
+
+        Ok(introspected_token)
+    }
+}
+
+// as per RFC 6749 section 2.3.1, the client credentials are form-urlencoded within Basic
+fn form_urlencode(value: &str) -> String {
+    form_urlencoded::byte_serialize(value.as_bytes()).collect()
+}
+
+fn cache_key(token: &str) -> Vec<u8> {
+    Sha256::digest(token.as_bytes()).to_vec()
+}
+
+fn is_expired(token: &ClaimsToken) -> bool {
+    match token.expires_at() {
+        Some(expires_at) => expires_at <= get_current_timestamp(),
+        None => true,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use axum::extract::State;
+    use axum::http::HeaderMap;
+    use axum::routing::post;
+    use axum::{Form, Json, Router};
+    use jsonwebtoken::get_current_timestamp;
+    use reqwest::Client;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+
+    use crate::auth::async_token_validator::AsyncTokenValidator;
+    use crate::auth::error_kind::{INTROSPECTION_FAILURE, INVALID_TOKEN};
+    use crate::auth::introspection_token_validator::{form_urlencode, IntrospectionTokenValidator};
+
+    // base64 of 'client:secret'
+    const EXPECTED_AUTHORIZATION: &str = "Basic Y2xpZW50OnNlY3JldA==";
+
+    #[tokio::test]
+    pub async fn validate_active_token_returns_claims() {
+        let (endpoint, _) = start_server().await;
+        let token_validator = get_token_validator(endpoint, "secret");
+
+        let token = token_validator
+            .validate("active-token")
+            .await
+            .expect("expected active token");
+
+        assert_eq!(Some("user-1".to_string()), token.subject());
+        assert_eq!(vec!["read", "write"], token.scopes());
+    }
+
+    #[tokio::test]
+    pub async fn validate_inactive_token_returns_invalid_token() {
+        let (endpoint, _) = start_server().await;
+        let token_validator = get_token_validator(endpoint, "secret");
+
+        let error = token_validator
+            .validate("revoked-token")
+            .await
+            .expect_err("expected inactive token");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn validate_with_wrong_client_secret_returns_introspection_failure() {
+        let (endpoint, _) = start_server().await;
+        let token_validator = get_token_validator(endpoint, "wrong");
+
+        let error = token_validator
+            .validate("active-token")
+            .await
+            .expect_err("expected unauthorized client");
+
+        assert_eq!(INTROSPECTION_FAILURE, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn introspect_active_token_is_cached() {
+        let (endpoint, hits) = start_server().await;
+        let token_validator = get_token_validator(endpoint, "secret");
+
+        for _ in 0..3 {
+            token_validator
+                .introspect("active-token")
+                .await
+                .expect("expected active token");
+        }
+
+        assert_eq!(1, hits.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    pub fn form_urlencode_encodes_reserved_characters() {
+        assert_eq!("client", form_urlencode("client"));
+        assert_eq!("client%3Aid+%C3%A9%25", form_urlencode("client:id é%"));
+    }
+
+    fn get_token_validator(endpoint: String, client_secret: &str) -> IntrospectionTokenValidator {
+        IntrospectionTokenValidator::new(
+            endpoint,
+            "client".to_string(),
+            client_secret.to_string(),
+            Client::new(),
+        )
+    }
+
+    async fn start_server() -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let router = Router::new()
+            .route("/introspect", post(introspect))
+            .with_state(hits.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("expected listener");
+        let address = listener.local_addr().expect("expected local address");
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        (format!("http://{}/introspect", address), hits)
+    }
+
+    async fn introspect(
+        State(hits): State<Arc<AtomicUsize>>,
+        headers: HeaderMap,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Result<Json<Value>, axum::http::StatusCode> {
+        hits.fetch_add(1, Ordering::SeqCst);
+
+        if headers
+            .get("Authorization")
+            .and_then(|value| value.to_str().ok())
+            != Some(EXPECTED_AUTHORIZATION)
+        {
+            return Err(axum::http::StatusCode::UNAUTHORIZED);
+        }
+
+        match form.get("token").map(String::as_str) {
+            Some("active-token") => Ok(Json(json!({
+                "active": true,
+                "sub": "user-1",
+                "scope": "read write",
+                "exp": get_current_timestamp() + 3600
+            }))),
+            _ => Ok(Json(json!({ "active": false }))),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+
+#[cfg(test)]
+use mockall::automock;
+
+/// `JwksProvider` provides the keys used for verifying the signature of JWTs.
+#[cfg_attr(test, automock)]
+#[async_trait]
+pub trait JwksProvider {
+    ///
+    /// Finds the key identified by `kid`.
+    ///
+    /// # Arguments
+    ///
+    /// * `kid` - Key ID specified within the token's header.
+    ///
+    /// # Returns
+    ///
+    /// * `Some` - The key identified by `kid`.
+    /// * `None` - If there is no known key for `kid`.
+    fn find(&self, kid: &str) -> Option<Jwk>;
+
+    ///
+    /// Same as `find`, but waits for the keys to be refreshed if `kid` is unknown, so the
+    /// first tokens signed with a rotated key are accepted. Providers with fixed keys do not
+    /// need to override it.
+    ///
+    /// # Arguments
+    ///
+    /// * `kid` - Key ID specified within the token's header.
+    ///
+    /// # Returns
+    ///
+    /// * `Some` - The key identified by `kid`.
+    /// * `None` - If there is no known key for `kid`, even after refreshing.
+    async fn find_or_refresh(&self, kid: &str) -> Option<Jwk> {
+        self.find(kid)
+    }
+}
+
+/// `StaticJwksProvider` provides the keys of a fixed `JwkSet`.
+pub struct StaticJwksProvider {
+    jwk_set: JwkSet,
+}
+
+impl StaticJwksProvider {
+    pub fn new(jwk_set: JwkSet) -> Self {
+        Self { jwk_set }
+    }
+}
+
+impl JwksProvider for StaticJwksProvider {
+    fn find(&self, kid: &str) -> Option<Jwk> {
+        self.jwk_set.find(kid).cloned()
+    }
+}
+
+/// `CompositeJwksProvider` looks for keys within several providers, i.e. one per issuer.
+pub struct CompositeJwksProvider {
+    jwks_providers: Vec<Arc<dyn JwksProvider + Send + Sync>>,
+}
+
+impl CompositeJwksProvider {
+    pub fn new(jwks_providers: Vec<Arc<dyn JwksProvider + Send + Sync>>) -> Self {
+        Self { jwks_providers }
+    }
+}
+
+#[async_trait]
+impl JwksProvider for CompositeJwksProvider {
+    fn find(&self, kid: &str) -> Option<Jwk> {
+        self.jwks_providers
+            .iter()
+            .find_map(|jwks_provider| jwks_provider.find(kid))
+    }
+
+    async fn find_or_refresh(&self, kid: &str) -> Option<Jwk> {
+        for jwks_provider in &self.jwks_providers {
+            if let Some(jwk) = jwks_provider.find_or_refresh(kid).await {
+                return Some(jwk);
+            }
+        }
+
+        None
+    }
+}
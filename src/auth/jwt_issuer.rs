@@ -0,0 +1,652 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{encode, get_current_timestamp, Algorithm, EncodingKey, Header};
+use ring::rand::SystemRandom;
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{
+    EcdsaKeyPair, EcdsaSigningAlgorithm, Ed25519KeyPair, KeyPair, RsaKeyPair,
+    ECDSA_P256_SHA256_FIXED_SIGNING, ECDSA_P384_SHA384_FIXED_SIGNING,
+};
+use serde_json::{json, Map, Value};
+
+use crate::auth::error_kind::{INVALID_KEY, SIGNING_FAILURE};
+use crate::auth::jwt_token_validator::JwtTokenValidator;
+use crate::auth::token::{
+    AUDIENCE_CLAIM, EXPIRATION_CLAIM, ISSUED_AT_CLAIM, ISSUER_CLAIM, SCOPE_CLAIM, SUBJECT_CLAIM,
+};
+use crate::error::Error;
+use crate::error_kind::NOT_FOUND;
+use crate::ok_or_return_error;
+
+const DEFAULT_LIFETIME_IN_SECONDS: u64 = 3600;
+const NOT_BEFORE_CLAIM: &str = "nbf";
+
+/// `SigningKey` is a private key used by `JwtIssuer`, along with its public `Jwk`.
+#[derive(Clone)]
+pub struct SigningKey {
+    algorithm: Algorithm,
+    encoding_key: EncodingKey,
+    jwk: Jwk,
+}
+
+impl SigningKey {
+    ///
+    /// Loads a private key from its PEM encoding.
+    ///
+    /// # Arguments
+    ///
+    /// * `kid` - ID of the key, written into the `kid` header of the issued tokens.
+    /// * `algorithm` - RSA, ECDSA or EdDSA signing algorithm.
+    /// * `pem` - PKCS#8 private key or, for RSA, PKCS#1 private key.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Signing key.
+    /// * `Err` - Error with the kind `INVALID_KEY` if the key cannot be used with `algorithm`.
+    pub fn from_pem(
+        kid: impl Into<String>,
+        algorithm: Algorithm,
+        pem: &[u8],
+    ) -> Result<Self, Error> {
+        let (label, der) = decode_pem(pem)?;
+
+        let encoding_key = match algorithm_family(algorithm)? {
+            KeyFamily::Rsa => EncodingKey::from_rsa_pem(pem),
+            KeyFamily::Ec(_, _) => EncodingKey::from_ec_pem(pem),
+            KeyFamily::Ed => EncodingKey::from_ed_pem(pem),
+        };
+        let encoding_key = ok_or_return_error!(
+            encoding_key,
+            INVALID_KEY,
+            "failed to read the private key: "
+        );
+
+        let parameters = public_parameters(algorithm, label.as_str(), der.as_slice())?;
+
+        Ok(Self::new(kid.into(), algorithm, encoding_key, parameters))
+    }
+
+    ///
+    /// Generates a new private key, i.e. for minting tokens within tests.
+    ///
+    /// # Arguments
+    ///
+    /// * `kid` - ID of the key, written into the `kid` header of the issued tokens.
+    /// * `algorithm` - `ES256`, `ES384` or `EdDSA`. RSA key generation is not supported.
+    pub fn generate(kid: impl Into<String>, algorithm: Algorithm) -> Result<Self, Error> {
+        let random = SystemRandom::new();
+
+        let (pkcs8, encoding_key) = match algorithm_family(algorithm)? {
+            KeyFamily::Ec(signing_algorithm, _) => {
+                let pkcs8 = ok_or_return_error!(
+                    EcdsaKeyPair::generate_pkcs8(signing_algorithm, &random),
+                    INVALID_KEY,
+                    "failed to generate the private key: "
+                );
+                let encoding_key = EncodingKey::from_ec_der(pkcs8.as_ref());
+
+                (pkcs8, encoding_key)
+            }
+            KeyFamily::Ed => {
+                let pkcs8 = ok_or_return_error!(
+                    Ed25519KeyPair::generate_pkcs8(&random),
+                    INVALID_KEY,
+                    "failed to generate the private key: "
+                );
+                let encoding_key = EncodingKey::from_ed_der(pkcs8.as_ref());
+
+                (pkcs8, encoding_key)
+            }
+            KeyFamily::Rsa => {
+                return Err(Error::new(
+                    INVALID_KEY,
+                    "RSA keys cannot be generated, load one through 'from_pem'",
+                ))
+            }
+        };
+
+        let parameters = public_parameters(algorithm, PKCS8_LABEL, pkcs8.as_ref())?;
+
+        Ok(Self::new(kid.into(), algorithm, encoding_key, parameters))
+    }
+
+    fn new(
+        kid: String,
+        algorithm: Algorithm,
+        encoding_key: EncodingKey,
+        parameters: AlgorithmParameters,
+    ) -> Self {
+        let jwk = Jwk {
+            common: CommonParameters {
+                public_key_use: Some(PublicKeyUse::Signature),
+                key_algorithm: KeyAlgorithm::from_str(format!("{:?}", algorithm).as_str()).ok(),
+                key_id: Some(kid),
+                ..CommonParameters::default()
+            },
+            algorithm: parameters,
+        };
+
+        Self {
+            algorithm,
+            encoding_key,
+            jwk,
+        }
+    }
+
+    pub fn kid(&self) -> &str {
+        self.jwk.common.key_id.as_deref().unwrap_or_default()
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    /// Public key, as published within the issuer's `JwkSet`.
+    pub fn jwk(&self) -> &Jwk {
+        &self.jwk
+    }
+}
+
+/// `JwtIssuer` signs JWTs with its `SigningKey`s and publishes the matching `JwkSet`, so
+/// tokens can be minted and validated without an external authorization server.
+///
+/// ```ignore
+/// let issuer = JwtIssuer::new("https://issuer.example/")
+///     .with_audience("cp-core")
+///     .with_signing_key(SigningKey::generate("key-1", Algorithm::ES256)?);
+///
+/// let token = issuer.token().subject("user-1").scope("read").expired().sign()?;
+/// ```
+#[derive(Clone)]
+pub struct JwtIssuer {
+    issuer: String,
+    audience: Vec<String>,
+    lifetime: Duration,
+    signing_keys: Vec<SigningKey>,
+}
+
+impl JwtIssuer {
+    pub fn new(issuer: impl Into<String>) -> Self {
+        Self {
+            issuer: issuer.into(),
+            audience: Vec::new(),
+            lifetime: Duration::from_secs(DEFAULT_LIFETIME_IN_SECONDS),
+            signing_keys: Vec::new(),
+        }
+    }
+
+    /// Adds a signing key. The first one is used unless the token specifies a `kid`.
+    pub fn with_signing_key(mut self, signing_key: SigningKey) -> Self {
+        self.signing_keys.push(signing_key);
+        self
+    }
+
+    /// Audience written into the `aud` claim of the issued tokens.
+    pub fn with_audience(mut self, audience: impl Into<String>) -> Self {
+        self.audience.push(audience.into());
+        self
+    }
+
+    /// Time the issued tokens are valid for. Defaults to one hour.
+    pub fn with_lifetime(mut self, lifetime: Duration) -> Self {
+        self.lifetime = lifetime;
+        self
+    }
+
+    pub fn issuer(&self) -> &str {
+        self.issuer.as_str()
+    }
+
+    /// Public keys of the issuer, i.e. for serving them as the JWKS URI.
+    pub fn jwk_set(&self) -> JwkSet {
+        JwkSet {
+            keys: self
+                .signing_keys
+                .iter()
+                .map(|signing_key| signing_key.jwk.clone())
+                .collect(),
+        }
+    }
+
+    /// Validator accepting the tokens of this issuer.
+    pub fn token_validator(&self) -> JwtTokenValidator {
+        JwtTokenValidator::new(
+            self.jwk_set(),
+            vec![self.issuer.clone()],
+            self.audience.clone(),
+        )
+    }
+
+    /// Starts a token with the `iss`, `aud`, `iat` and `exp` claims already set.
+    pub fn token(&self) -> JwtBuilder<'_> {
+        let now = get_current_timestamp();
+        let mut claims = Map::new();
+        claims.insert(ISSUER_CLAIM.to_string(), json!(self.issuer));
+        claims.insert(ISSUED_AT_CLAIM.to_string(), json!(now));
+        claims.insert(
+            EXPIRATION_CLAIM.to_string(),
+            json!(now + self.lifetime.as_secs()),
+        );
+
+        match self.audience.as_slice() {
+            [] => {}
+            [audience] => {
+                claims.insert(AUDIENCE_CLAIM.to_string(), json!(audience));
+            }
+            audience => {
+                claims.insert(AUDIENCE_CLAIM.to_string(), json!(audience));
+            }
+        }
+
+        JwtBuilder {
+            issuer: self,
+            kid: None,
+            token_type: None,
+            claims,
+        }
+    }
+
+    ///
+    /// Issues a token with the specified claims on top of the default ones.
+    ///
+    /// # Arguments
+    ///
+    /// * `claims` - JSON object whose members are written as claims, overriding the defaults.
+    pub fn issue(&self, claims: Value) -> Result<String, Error> {
+        let mut builder = self.token();
+
+        if let Value::Object(claims) = claims {
+            for (name, value) in claims {
+                builder = builder.claim(name, value);
+            }
+        }
+
+        builder.sign()
+    }
+
+    fn signing_key(&self, kid: Option<&str>) -> Result<&SigningKey, Error> {
+        let signing_key = match kid {
+            Some(kid) => self
+                .signing_keys
+                .iter()
+                .find(|signing_key| signing_key.kid() == kid),
+            None => self.signing_keys.first(),
+        };
+
+        match signing_key {
+            Some(signing_key) => Ok(signing_key),
+            None => Err(Error::new(
+                NOT_FOUND,
+                format!("could not find signing key '{}'", kid.unwrap_or_default()),
+            )),
+        }
+    }
+}
+
+/// `JwtBuilder` holds the claims of a token to be signed by its `JwtIssuer`.
+pub struct JwtBuilder<'a> {
+    issuer: &'a JwtIssuer,
+    kid: Option<String>,
+    token_type: Option<String>,
+    claims: Map<String, Value>,
+}
+
+impl<'a> JwtBuilder<'a> {
+    /// Signs with the key `kid`, which must belong to the issuer.
+    pub fn signed_with(mut self, kid: impl Into<String>) -> Self {
+        self.kid = Some(kid.into());
+        self
+    }
+
+    /// Sets the `typ` header, i.e. `at+jwt`.
+    pub fn token_type(mut self, token_type: impl Into<String>) -> Self {
+        self.token_type = Some(token_type.into());
+        self
+    }
+
+    pub fn claim(mut self, name: impl Into<String>, value: Value) -> Self {
+        self.claims.insert(name.into(), value);
+        self
+    }
+
+    /// Removes a claim, including the default ones.
+    pub fn without_claim(mut self, name: &str) -> Self {
+        self.claims.remove(name);
+        self
+    }
+
+    pub fn subject(self, subject: impl Into<String>) -> Self {
+        self.claim(SUBJECT_CLAIM, json!(subject.into()))
+    }
+
+    pub fn audience(self, audience: impl Into<String>) -> Self {
+        self.claim(AUDIENCE_CLAIM, json!(audience.into()))
+    }
+
+    /// Sets the space-separated `scope` claim.
+    pub fn scope(self, scope: impl Into<String>) -> Self {
+        self.claim(SCOPE_CLAIM, json!(scope.into()))
+    }
+
+    pub fn issued_at(self, issued_at: u64) -> Self {
+        self.claim(ISSUED_AT_CLAIM, json!(issued_at))
+    }
+
+    pub fn not_before(self, not_before: u64) -> Self {
+        self.claim(NOT_BEFORE_CLAIM, json!(not_before))
+    }
+
+    pub fn expires_at(self, expires_at: u64) -> Self {
+        self.claim(EXPIRATION_CLAIM, json!(expires_at))
+    }
+
+    pub fn expires_in(self, expires_in: Duration) -> Self {
+        self.expires_at(get_current_timestamp() + expires_in.as_secs())
+    }
+
+    /// Makes the token expired for an hour, well beyond any reasonable leeway.
+    pub fn expired(self) -> Self {
+        let now = get_current_timestamp();
+
+        self.issued_at(now - 2 * DEFAULT_LIFETIME_IN_SECONDS)
+            .expires_at(now - DEFAULT_LIFETIME_IN_SECONDS)
+    }
+
+    ///
+    /// Signs the token.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Encoded JWT.
+    /// * `Err` - Error with the kind `NOT_FOUND` if the issuer has no key matching the `kid`,
+    ///   or `SIGNING_FAILURE` if the token could not be signed.
+    pub fn sign(self) -> Result<String, Error> {
+        let signing_key = self.issuer.signing_key(self.kid.as_deref())?;
+        let header = Header {
+            typ: Some(self.token_type.unwrap_or("JWT".to_string())),
+            kid: Some(signing_key.kid().to_string()),
+            ..Header::new(signing_key.algorithm)
+        };
+
+        Ok(ok_or_return_error!(
+            encode(&header, &self.claims, &signing_key.encoding_key),
+            SIGNING_FAILURE,
+            "failed to sign the token: "
+        ))
+    }
+}
+
+const PKCS8_LABEL: &str = "PRIVATE KEY";
+const PKCS1_LABEL: &str = "RSA PRIVATE KEY";
+
+enum KeyFamily {
+    Rsa,
+    Ec(&'static EcdsaSigningAlgorithm, EllipticCurve),
+    Ed,
+}
+
+fn algorithm_family(algorithm: Algorithm) -> Result<KeyFamily, Error> {
+    match algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => Ok(KeyFamily::Rsa),
+        Algorithm::ES256 => Ok(KeyFamily::Ec(
+            &ECDSA_P256_SHA256_FIXED_SIGNING,
+            EllipticCurve::P256,
+        )),
+        Algorithm::ES384 => Ok(KeyFamily::Ec(
+            &ECDSA_P384_SHA384_FIXED_SIGNING,
+            EllipticCurve::P384,
+        )),
+        Algorithm::EdDSA => Ok(KeyFamily::Ed),
+        _ => Err(Error::new(
+            INVALID_KEY,
+            format!("algorithm '{:?}' is not asymmetric", algorithm),
+        )),
+    }
+}
+
+fn public_parameters(
+    algorithm: Algorithm,
+    label: &str,
+    der: &[u8],
+) -> Result<AlgorithmParameters, Error> {
+    match algorithm_family(algorithm)? {
+        KeyFamily::Rsa => {
+            let key_pair = match label {
+                PKCS1_LABEL => RsaKeyPair::from_der(der),
+                _ => RsaKeyPair::from_pkcs8(der),
+            };
+            let key_pair = ok_or_return_error!(
+                key_pair,
+                INVALID_KEY,
+                "failed to read the RSA private key: "
+            );
+            let components: PublicKeyComponents<Vec<u8>> = key_pair.public().into();
+
+            Ok(AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(components.n),
+                e: URL_SAFE_NO_PAD.encode(components.e),
+            }))
+        }
+        KeyFamily::Ec(signing_algorithm, curve) => {
+            let key_pair = ok_or_return_error!(
+                EcdsaKeyPair::from_pkcs8(signing_algorithm, der, &SystemRandom::new()),
+                INVALID_KEY,
+                "failed to read the EC private key: "
+            );
+            // uncompressed point: 0x04 || x || y
+            let point = &key_pair.public_key().as_ref()[1..];
+            let (x, y) = point.split_at(point.len() / 2);
+
+            Ok(AlgorithmParameters::EllipticCurve(
+                EllipticCurveKeyParameters {
+                    key_type: EllipticCurveKeyType::EC,
+                    curve,
+                    x: URL_SAFE_NO_PAD.encode(x),
+                    y: URL_SAFE_NO_PAD.encode(y),
+                },
+            ))
+        }
+        KeyFamily::Ed => {
+            let key_pair = ok_or_return_error!(
+                Ed25519KeyPair::from_pkcs8_maybe_unchecked(der),
+                INVALID_KEY,
+                "failed to read the Ed25519 private key: "
+            );
+
+            Ok(AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key_pair.public_key().as_ref()),
+            }))
+        }
+    }
+}
+
+fn decode_pem(pem: &[u8]) -> Result<(String, Vec<u8>), Error> {
+    let pem = ok_or_return_error!(
+        std::str::from_utf8(pem),
+        INVALID_KEY,
+        "private key is not valid PEM: "
+    );
+
+    let label = match pem
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("-----"))
+    {
+        Some(label) => label.to_string(),
+        None => return Err(Error::new(INVALID_KEY, "private key is not valid PEM")),
+    };
+
+    let content: String = pem
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("-----"))
+        .collect();
+
+    let der = ok_or_return_error!(
+        STANDARD.decode(content),
+        INVALID_KEY,
+        "private key is not valid PEM: "
+    );
+
+    Ok((label, der))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::time::Duration;
+
+    use jsonwebtoken::jwk::AlgorithmParameters;
+    use jsonwebtoken::Algorithm;
+    use serde_json::json;
+
+    use crate::auth::error_kind::{INVALID_KEY, INVALID_TOKEN};
+    use crate::auth::jwt_issuer::{JwtIssuer, SigningKey};
+    use crate::auth::token_validator::TokenValidator;
+    use crate::error_kind::NOT_FOUND;
+    use crate::test_base::get_unit_test_data_path;
+
+    #[test]
+    pub fn issued_tokens_are_accepted_by_token_validator() {
+        let issuer = get_issuer();
+
+        for kid in ["rs256", "es256", "es384", "eddsa"] {
+            let token = issuer
+                .token()
+                .signed_with(kid)
+                .subject("user-1")
+                .scope("read write")
+                .sign()
+                .expect("expected signed token");
+
+            let validated_token = issuer
+                .token_validator()
+                .validate(token.as_str())
+                .unwrap_or_else(|_| panic!("expected token signed with '{}' to be valid", kid));
+
+            assert_eq!(Some("user-1".to_string()), validated_token.subject());
+            assert_eq!(vec!["read", "write"], validated_token.scopes());
+        }
+    }
+
+    #[test]
+    pub fn expired_token_is_rejected_by_token_validator() {
+        let issuer = get_issuer();
+        let token = issuer
+            .token()
+            .subject("user-1")
+            .expired()
+            .sign()
+            .expect("expected signed token");
+
+        let error = issuer
+            .token_validator()
+            .validate(token.as_str())
+            .expect_err("expected expired token");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn issue_overrides_default_claims() {
+        let issuer = get_issuer().with_lifetime(Duration::from_secs(60));
+        let token = issuer
+            .issue(json!({ "sub": "user-1", "aud": "other-audience" }))
+            .expect("expected signed token");
+
+        let error = issuer
+            .token_validator()
+            .validate(token.as_str())
+            .expect_err("expected invalid audience");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn jwk_set_publishes_public_keys() {
+        let jwk_set = get_issuer().jwk_set();
+
+        assert_eq!(4, jwk_set.keys.len());
+        let rsa_jwk = jwk_set.find("rs256").expect("expected 'rs256' jwk");
+        assert!(matches!(rsa_jwk.algorithm, AlgorithmParameters::RSA(_)));
+        let serialized_jwk_set = serde_json::to_string(&jwk_set).expect("expected JSON");
+        assert!(!serialized_jwk_set.contains("\"d\""));
+    }
+
+    #[test]
+    pub fn sign_with_unknown_kid_returns_not_found() {
+        let error = get_issuer()
+            .token()
+            .signed_with("unknown")
+            .sign()
+            .expect_err("expected unknown kid");
+
+        assert_eq!(NOT_FOUND, error.error_kind());
+    }
+
+    #[test]
+    pub fn invalid_keys_return_error() {
+        let pem = read_test_file("rs256_private_key.pem");
+
+        let errors = [
+            SigningKey::from_pem("es256", Algorithm::ES256, pem.as_slice()).err(),
+            SigningKey::from_pem("hs256", Algorithm::HS256, pem.as_slice()).err(),
+            SigningKey::generate("rs256", Algorithm::RS256).err(),
+        ];
+
+        for error in errors {
+            assert_eq!(
+                INVALID_KEY,
+                error.expect("expected invalid key").error_kind()
+            );
+        }
+    }
+
+    fn get_issuer() -> JwtIssuer {
+        JwtIssuer::new("https://issuer.example/")
+            .with_audience("cp-core")
+            .with_signing_key(
+                SigningKey::from_pem(
+                    "rs256",
+                    Algorithm::RS256,
+                    read_test_file("rs256_private_key.pem").as_slice(),
+                )
+                .expect("expected RSA signing key"),
+            )
+            .with_signing_key(
+                SigningKey::generate("es256", Algorithm::ES256).expect("expected ES256 key"),
+            )
+            .with_signing_key(
+                SigningKey::generate("es384", Algorithm::ES384).expect("expected ES384 key"),
+            )
+            .with_signing_key(
+                SigningKey::generate("eddsa", Algorithm::EdDSA).expect("expected EdDSA key"),
+            )
+    }
+
+    fn read_test_file(file_name: &str) -> Vec<u8> {
+        let mut path = get_unit_test_data_path(file!());
+        path.push(file_name);
+
+        std::fs::read(path).expect("expected test file")
+    }
+}
@@ -2,7 +2,9 @@
  * Copyright (c) Gabriel Amihalachioaie, SimpleG 2023.
  */
 
-use crate::auth::token::Token;
+use crate::auth::token::{Token, ISSUER_CLAIM};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use jsonwebtoken::TokenData;
 use serde_json::Value;
 use std::collections::HashMap;
@@ -24,4 +26,103 @@ impl Debug for JwtToken {
     }
 }
 
-impl Token for JwtToken {}
+impl Token for JwtToken {
+    fn claim_value(&self, name: &str) -> Option<Value> {
+        self.token_data.claims.get(name).cloned()
+    }
+}
+
+/// Reads the `iss` claim of a JWT without verifying its signature, i.e. for routing the token to
+/// the validator of its issuer.
+pub(crate) fn unverified_issuer(token: &str) -> Option<String> {
+    let mut segments = token.split('.');
+    let payload = match (
+        segments.next(),
+        segments.next(),
+        segments.next(),
+        segments.next(),
+    ) {
+        (Some(_), Some(payload), Some(_), None) => payload,
+        _ => return None,
+    };
+
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let claims = serde_json::from_slice::<HashMap<String, Value>>(payload.as_slice()).ok()?;
+
+    match claims.get(ISSUER_CLAIM) {
+        Some(Value::String(issuer)) => Some(issuer.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::Arc;
+
+    use serde_json::json;
+
+    use crate::auth::token::Token;
+    use crate::error_kind::{NOT_FOUND, SERIALIZATION_FAILURE};
+    use crate::test_base::get_jwt_token;
+
+    #[test]
+    pub fn standard_claims_are_exposed() {
+        let token = get_jwt_token(json!({
+            "sub": "user-1",
+            "iss": "https://issuer.example/",
+            "aud": ["api", "cp-config"],
+            "exp": 1700000000u64,
+            "iat": 1690000000u64,
+            "scope": "read:config write:config"
+        }));
+
+        assert_eq!(Some("user-1".to_string()), token.subject());
+        assert_eq!(Some("https://issuer.example/".to_string()), token.issuer());
+        assert_eq!(vec!["api", "cp-config"], token.audience());
+        assert_eq!(Some(1700000000), token.expires_at());
+        assert_eq!(Some(1690000000), token.issued_at());
+        assert_eq!(vec!["read:config", "write:config"], token.scopes());
+    }
+
+    #[test]
+    pub fn scopes_falls_back_to_scp_claim() {
+        let token = get_jwt_token(json!({ "scp": ["read", "write"], "aud": "api" }));
+
+        assert_eq!(vec!["read", "write"], token.scopes());
+        assert_eq!(vec!["api"], token.audience());
+    }
+
+    #[test]
+    pub fn claim_deserializes_custom_claim() {
+        let token: Arc<dyn Token + Send + Sync> =
+            Arc::new(get_jwt_token(json!({ "roles": ["admin", "reader"] })));
+
+        let roles = token
+            .claim::<Vec<String>>("roles")
+            .expect("expected 'roles' claim");
+
+        assert_eq!(vec!["admin", "reader"], roles);
+    }
+
+    #[test]
+    pub fn claim_missing_returns_not_found() {
+        let token: Arc<dyn Token + Send + Sync> = Arc::new(get_jwt_token(json!({})));
+
+        let error = token
+            .claim::<String>("email")
+            .expect_err("expected missing claim error");
+
+        assert_eq!(NOT_FOUND, error.error_kind());
+    }
+
+    #[test]
+    pub fn claim_with_unexpected_type_returns_serialization_failure() {
+        let token: Arc<dyn Token + Send + Sync> = Arc::new(get_jwt_token(json!({ "email": 5 })));
+
+        let error = token
+            .claim::<String>("email")
+            .expect_err("expected deserialization error");
+
+        assert_eq!(SERIALIZATION_FAILURE, error.error_kind());
+    }
+}
@@ -2,100 +2,684 @@
  * Copyright (c) Gabriel Amihalachioaie, SimpleG 2023.
  */
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 
-use jsonwebtoken::jwk::{AlgorithmParameters, JwkSet};
-use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use async_trait::async_trait;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet};
+use jsonwebtoken::{
+    decode, decode_header, get_current_timestamp, Algorithm, DecodingKey, Header, Validation,
+};
+use reqwest::Client;
 use serde_json::Value;
 
-use crate::auth::error_kind::{INVALID_TOKEN, JWKS_RETRIEVAL_FAILURE, MALFORMED_TOKEN};
-use crate::auth::jwt_token::JwtToken;
-use crate::auth::token::Token;
+use crate::auth::async_token_validator::AsyncTokenValidator;
+use crate::auth::error_kind::{
+    DISCOVERY_FAILURE, INVALID_ALGORITHM, INVALID_TOKEN, JWKS_RETRIEVAL_FAILURE, MALFORMED_TOKEN,
+};
+use crate::auth::jwks_provider::{JwksProvider, StaticJwksProvider};
+use crate::auth::jwt_token::{unverified_issuer, JwtToken};
+use crate::auth::jwt_validation_options::JwtValidationOptions;
+use crate::auth::openid_discovery::try_get_openid_configuration;
+use crate::auth::remote_jwks_provider::{RemoteJwksOptions, RemoteJwksProvider};
+use crate::auth::token::{Token, ISSUED_AT_CLAIM};
+use crate::auth::token_cache::TokenCache;
 use crate::auth::token_validator::TokenValidator;
 use crate::error::Error;
+use crate::secrets::secrets_manager::SecretsManager;
 use crate::{ok_or_return_error, some_or_return_error};
 
+const RSA_ALGORITHMS: [Algorithm; 6] = [
+    Algorithm::RS256,
+    Algorithm::RS384,
+    Algorithm::RS512,
+    Algorithm::PS256,
+    Algorithm::PS384,
+    Algorithm::PS512,
+];
+const HMAC_ALGORITHMS: [Algorithm; 3] = [Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+// the keys of rotated out 'kid's are dropped once this many decoding keys are cached
+const MAX_DECODING_KEYS: usize = 64;
+
 pub struct JwtTokenValidator {
-    jwk_set: JwkSet,
+    key_source: KeySource,
     issuers: Vec<String>,
     audience: Vec<String>,
+    options: JwtValidationOptions,
+    token_cache: Option<TokenCache>,
+}
+
+type CachedDecodingKey = (Jwk, DecodingKey, Algorithm);
+
+enum KeySource {
+    Jwks {
+        jwks_provider: Arc<dyn JwksProvider + Send + Sync>,
+        decoding_keys: Mutex<HashMap<(String, Algorithm), CachedDecodingKey>>,
+    },
+    // tokens are only verified with the keys and algorithms of the issuer they claim
+    Issuers {
+        issuer_keys: HashMap<String, IssuerKeys>,
+        decoding_keys: Mutex<HashMap<(String, Algorithm), CachedDecodingKey>>,
+    },
+    Secret {
+        decoding_key: DecodingKey,
+        algorithms: Vec<Algorithm>,
+    },
+}
+
+struct IssuerKeys {
+    jwks_provider: Arc<dyn JwksProvider + Send + Sync>,
+    algorithms: Vec<Algorithm>,
 }
 
 impl JwtTokenValidator {
     pub fn new(jwk_set: JwkSet, issuers: Vec<String>, audience: Vec<String>) -> Self {
+        Self::with_jwks_provider(
+            Arc::new(StaticJwksProvider::new(jwk_set)),
+            issuers,
+            audience,
+        )
+    }
+
+    /// Creates a validator which gets its keys from `jwks_provider`, i.e. a `RemoteJwksProvider`
+    /// for following the key rotations of the identity provider.
+    pub fn with_jwks_provider(
+        jwks_provider: Arc<dyn JwksProvider + Send + Sync>,
+        issuers: Vec<String>,
+        audience: Vec<String>,
+    ) -> Self {
         Self {
-            jwk_set,
+            key_source: KeySource::Jwks {
+                jwks_provider,
+                decoding_keys: Mutex::new(HashMap::new()),
+            },
             issuers,
             audience,
+            options: JwtValidationOptions::default(),
+            token_cache: None,
         }
     }
-}
 
-impl TokenValidator for JwtTokenValidator {
-    fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
-        let header = ok_or_return_error!(
-            decode_header(token),
-            MALFORMED_TOKEN,
-            "failed to decode token's header: "
-        );
-
-        let kid =
-            some_or_return_error!(header.kid, MALFORMED_TOKEN, "'kid' is missing from header");
+    ///
+    /// Creates a validator by discovering the OpenID Provider metadata of each issuer. Tokens
+    /// are only verified with the keys and signing algorithms of the issuer named by their `iss`
+    /// claim, and rejected if it is not one of the discovered issuers. The keys are refreshed as
+    /// described by `RemoteJwksProvider`.
+    ///
+    /// # Arguments
+    ///
+    /// * `issuer_urls` - Issuers whose tokens are accepted, i.e. `https://simpleg.eu.auth0.com/`.
+    /// * `audience` - Accepted audience.
+    /// * `client` - HTTP client used for the requests.
+    /// * `options` - Timing settings of the keys' cache.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validator.
+    /// * `Err` - Error with the kind `DISCOVERY_FAILURE` or `JWKS_RETRIEVAL_FAILURE` if the
+    ///   metadata or keys of any issuer could not be retrieved, or if an issuer does not list
+    ///   any supported signing algorithm.
+    pub async fn from_discovery(
+        issuer_urls: &[&str],
+        audience: Vec<String>,
+        client: Client,
+        options: RemoteJwksOptions,
+    ) -> Result<Self, Error> {
+        let mut issuers: Vec<String> = Vec::new();
+        let mut algorithms: Vec<Algorithm> = Vec::new();
+        let mut issuer_keys: HashMap<String, IssuerKeys> = HashMap::new();
+        let mut jwks_providers: HashMap<String, Arc<dyn JwksProvider + Send + Sync>> =
+            HashMap::new();
 
-        let jwk = some_or_return_error!(
-            self.jwk_set.find(&kid),
-            MALFORMED_TOKEN,
-            "could not find 'kid' within 'jwk_set'"
-        );
+        for issuer_url in issuer_urls {
+            let configuration = try_get_openid_configuration(&client, issuer_url).await?;
+            // shared secrets cannot be published within a JWKS
+            let issuer_algorithms: Vec<Algorithm> = configuration
+                .algorithms()
+                .into_iter()
+                .filter(|algorithm| !HMAC_ALGORITHMS.contains(algorithm))
+                .collect();
 
-        let rsa = match jwk.algorithm {
-            AlgorithmParameters::RSA(ref rsa) => rsa,
-            _ => {
+            if issuer_algorithms.is_empty() {
                 return Err(Error::new(
-                    MALFORMED_TOKEN,
+                    DISCOVERY_FAILURE,
                     format!(
-                        "got an unexpected algorithm for the 'jwk': {:?}",
-                        jwk.algorithm
+                        "issuer '{}' does not list any supported signing algorithm",
+                        configuration.issuer
                     ),
                 ));
             }
+
+            for algorithm in &issuer_algorithms {
+                if !algorithms.contains(algorithm) {
+                    algorithms.push(*algorithm);
+                }
+            }
+
+            let jwks_provider = match jwks_providers.get(&configuration.jwks_uri) {
+                Some(jwks_provider) => jwks_provider.clone(),
+                None => {
+                    let jwks_provider: Arc<dyn JwksProvider + Send + Sync> = Arc::new(
+                        RemoteJwksProvider::try_new(
+                            configuration.jwks_uri.clone(),
+                            client.clone(),
+                            options.clone(),
+                        )
+                        .await?,
+                    );
+                    jwks_providers.insert(configuration.jwks_uri.clone(), jwks_provider.clone());
+
+                    jwks_provider
+                }
+            };
+
+            issuers.push(configuration.issuer.clone());
+            issuer_keys.insert(
+                configuration.issuer,
+                IssuerKeys {
+                    jwks_provider,
+                    algorithms: issuer_algorithms,
+                },
+            );
+        }
+
+        let token_validator = Self {
+            key_source: KeySource::Issuers {
+                issuer_keys,
+                decoding_keys: Mutex::new(HashMap::new()),
+            },
+            issuers,
+            audience,
+            options: JwtValidationOptions::default().with_algorithms(algorithms),
+            token_cache: None,
         };
 
-        let decoding_key = ok_or_return_error!(
-            DecodingKey::from_rsa_components(&rsa.n, &rsa.e),
-            MALFORMED_TOKEN,
-            "failed to get decoding key: "
-        );
+        Ok(token_validator)
+    }
 
-        let key_algorithm = some_or_return_error!(
-            jwk.common.key_algorithm,
-            MALFORMED_TOKEN,
-            "'jwk' is missing the 'key_algorithm'"
-        );
+    ///
+    /// Creates a validator for tokens signed with a shared secret (HS256, HS384 or HS512),
+    /// intended for service-to-service tokens.
+    ///
+    /// # Arguments
+    ///
+    /// * `secret` - Shared secret used for signing the tokens.
+    /// * `algorithms` - Accepted HMAC algorithms.
+    /// * `issuers` - Accepted issuers.
+    /// * `audience` - Accepted audience.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Validator.
+    /// * `Err` - Error with the kind `INVALID_ALGORITHM` if `algorithms` is empty or contains
+    ///   a non HMAC algorithm.
+    pub fn with_secret(
+        secret: &[u8],
+        algorithms: Vec<Algorithm>,
+        issuers: Vec<String>,
+        audience: Vec<String>,
+    ) -> Result<Self, Error> {
+        if algorithms.is_empty() {
+            return Err(Error::new(
+                INVALID_ALGORITHM,
+                "at least one HMAC algorithm must be specified",
+            ));
+        }
 
-        let algorithm = ok_or_return_error!(
-            Algorithm::from_str(key_algorithm.to_string().as_str()),
-            MALFORMED_TOKEN,
-            "failed to get algorithm for 'key_algorithm': "
-        );
+        if let Some(algorithm) = algorithms
+            .iter()
+            .find(|algorithm| !HMAC_ALGORITHMS.contains(algorithm))
+        {
+            return Err(Error::new(
+                INVALID_ALGORITHM,
+                format!("'{:?}' is not an HMAC algorithm", algorithm),
+            ));
+        }
+
+        Ok(Self {
+            key_source: KeySource::Secret {
+                decoding_key: DecodingKey::from_secret(secret),
+                algorithms,
+            },
+            issuers,
+            audience,
+            options: JwtValidationOptions::default(),
+            token_cache: None,
+        })
+    }
+
+    ///
+    /// Creates a validator for tokens signed with a shared secret stored within the
+    /// secrets manager.
+    ///
+    /// # Arguments
+    ///
+    /// * `secrets_manager` - Secrets manager holding the shared secret.
+    /// * `secret_id` - ID of the shared secret within the secrets manager.
+    /// * `algorithms` - Accepted HMAC algorithms.
+    /// * `issuers` - Accepted issuers.
+    /// * `audience` - Accepted audience.
+    pub fn from_secrets_manager(
+        secrets_manager: &dyn SecretsManager,
+        secret_id: &str,
+        algorithms: Vec<Algorithm>,
+        issuers: Vec<String>,
+        audience: Vec<String>,
+    ) -> Result<Self, Error> {
+        let secret = secrets_manager.get_secret(secret_id)?;
+
+        Self::with_secret(secret.as_bytes(), algorithms, issuers, audience)
+    }
+
+    ///
+    /// Replaces the validation rules. If `options` has no algorithm allow-list, the algorithms
+    /// pinned by `from_discovery` are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `options` - Validation rules such as leeway, required claims or accepted algorithms.
+    pub fn with_options(mut self, options: JwtValidationOptions) -> Self {
+        self.options = if options.algorithms().is_empty() {
+            let algorithms = self.options.algorithms().to_vec();
+            options.with_algorithms(algorithms)
+        } else {
+            options
+        };
 
+        self
+    }
+
+    ///
+    /// Caches the validated tokens, so repeated validations of the same token skip the
+    /// signature verification until the token expires.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Maximum number of cached tokens, the least recently used being evicted.
+    pub fn with_token_cache(mut self, capacity: usize) -> Self {
+        self.token_cache = Some(TokenCache::new(capacity));
+        self
+    }
+
+    /// Accepted issuers, i.e. for routing tokens by their `iss` claim.
+    pub fn issuers(&self) -> &[String] {
+        self.issuers.as_slice()
+    }
+
+    fn validation(&self, algorithm: Algorithm) -> Validation {
         let mut validation = Validation::new(algorithm);
         validation.validate_exp = true;
+        validation.validate_nbf = self.options.validate_nbf();
+        validation.leeway = self.options.leeway().as_secs();
+        validation.set_required_spec_claims(self.options.required_claims());
         validation.set_issuer(self.issuers.as_slice());
         validation.set_audience(self.audience.as_slice());
 
+        validation
+    }
+
+    fn validate_claims(&self, claims: &HashMap<String, Value>) -> Result<(), Error> {
+        if let Some(claim) = self
+            .options
+            .required_claims()
+            .iter()
+            .find(|claim| !claims.contains_key(claim.as_str()))
+        {
+            return Err(Error::new(
+                INVALID_TOKEN,
+                format!("token is missing the required claim '{}'", claim),
+            ));
+        }
+
+        if let Some(max_age) = self.options.max_age() {
+            let issued_at = some_or_return_error!(
+                claims.get(ISSUED_AT_CLAIM).and_then(|value| value.as_u64()),
+                INVALID_TOKEN,
+                "token is missing the 'iat' claim required for validating its age"
+            );
+            let now = get_current_timestamp();
+            let leeway = self.options.leeway().as_secs();
+
+            if issued_at > now + leeway {
+                return Err(Error::new(INVALID_TOKEN, "token was issued in the future"));
+            }
+
+            if now.saturating_sub(issued_at) > max_age.as_secs() + leeway {
+                return Err(Error::new(
+                    INVALID_TOKEN,
+                    "token is older than the maximum age allowed",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    // tokens are cached until they expire or become older than the maximum age allowed
+    fn cache_expiration(&self, token: &(dyn Token + Send + Sync)) -> Option<u64> {
+        let expires_at = token.expires_at()?;
+
+        match (self.options.max_age(), token.issued_at()) {
+            (Some(max_age), Some(issued_at)) => Some(expires_at.min(issued_at + max_age.as_secs())),
+            _ => Some(expires_at),
+        }
+    }
+
+    // cached tokens are only returned while the key which validated them is still provided, so
+    // that tokens of a 'kid' removed from the key set or whose key was rotated are not accepted
+    // until they expire
+    fn cached_token(&self, token: &str, jwk: Option<&Jwk>) -> Option<Arc<dyn Token + Send + Sync>> {
+        self.token_cache.as_ref()?.get(token, jwk)
+    }
+
+    fn checked_header(&self, token: &str) -> Result<Header, Error> {
+        let header = ok_or_return_error!(
+            decode_header(token),
+            MALFORMED_TOKEN,
+            "failed to decode token's header: "
+        );
+
+        if !self.options.is_token_type_allowed(header.typ.as_deref()) {
+            return Err(Error::new(
+                INVALID_TOKEN,
+                format!("unexpected token type '{:?}'", header.typ),
+            ));
+        }
+
+        if !self.options.is_algorithm_allowed(&header.alg) {
+            return Err(Error::new(
+                INVALID_TOKEN,
+                format!("algorithm '{:?}' is not allowed", header.alg),
+            ));
+        }
+
+        Ok(header)
+    }
+
+    // gets the provider of the token's keys, if they are not a shared secret
+    fn jwks_provider(
+        &self,
+        token: &str,
+        header: &Header,
+    ) -> Result<Option<&Arc<dyn JwksProvider + Send + Sync>>, Error> {
+        match self.key_source {
+            KeySource::Jwks {
+                ref jwks_provider, ..
+            } => Ok(Some(jwks_provider)),
+            KeySource::Issuers {
+                ref issuer_keys, ..
+            } => {
+                let issuer = some_or_return_error!(
+                    unverified_issuer(token),
+                    INVALID_TOKEN,
+                    "token is missing the 'iss' claim"
+                );
+
+                let issuer_keys = some_or_return_error!(
+                    issuer_keys.get(&issuer),
+                    INVALID_TOKEN,
+                    format!("issuer '{}' is not accepted", issuer)
+                );
+
+                if !issuer_keys.algorithms.contains(&header.alg) {
+                    return Err(Error::new(
+                        INVALID_TOKEN,
+                        format!(
+                            "algorithm '{:?}' is not allowed for issuer '{}'",
+                            header.alg, issuer
+                        ),
+                    ));
+                }
+
+                Ok(Some(&issuer_keys.jwks_provider))
+            }
+            KeySource::Secret { .. } => Ok(None),
+        }
+    }
+
+    // 'jwk' is the key found for the header's 'kid', if the keys come from a 'JwksProvider'
+    fn validate_with_key(
+        &self,
+        token: &str,
+        header: Header,
+        jwk: Option<Jwk>,
+    ) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let decoding_key = match self.key_source {
+            KeySource::Jwks {
+                ref decoding_keys, ..
+            }
+            | KeySource::Issuers {
+                ref decoding_keys, ..
+            } => {
+                let kid = kid(&header)?.to_string();
+
+                let jwk = some_or_return_error!(
+                    jwk.as_ref(),
+                    MALFORMED_TOKEN,
+                    "could not find 'kid' within 'jwk_set'"
+                );
+
+                let (decoding_key, algorithm) =
+                    cached_decoding_key(decoding_keys, kid, jwk.clone(), header.alg)?;
+
+                if algorithm != header.alg {
+                    return Err(Error::new(
+                        INVALID_TOKEN,
+                        format!(
+                            "algorithm '{:?}' does not match the key's algorithm '{:?}'",
+                            header.alg, algorithm
+                        ),
+                    ));
+                }
+
+                Cow::Owned(decoding_key)
+            }
+            KeySource::Secret {
+                ref decoding_key,
+                ref algorithms,
+            } => {
+                if !algorithms.contains(&header.alg) {
+                    return Err(Error::new(
+                        INVALID_TOKEN,
+                        format!("algorithm '{:?}' is not allowed", header.alg),
+                    ));
+                }
+
+                Cow::Borrowed(decoding_key)
+            }
+        };
+
+        let validation = self.validation(header.alg);
+
         let decoded_token = ok_or_return_error!(
-            decode::<HashMap<String, Value>>(token, &decoding_key, &validation),
+            decode::<HashMap<String, Value>>(token, decoding_key.as_ref(), &validation),
             INVALID_TOKEN,
             "failed to validate token: "
         );
 
-        Ok(Arc::new(JwtToken::new(decoded_token)))
+        self.validate_claims(&decoded_token.claims)?;
+
+        let validated_token: Arc<dyn Token + Send + Sync> = Arc::new(JwtToken::new(decoded_token));
+
+        if let Some(ref token_cache) = self.token_cache {
+            if let Some(expires_at) = self.cache_expiration(validated_token.as_ref()) {
+                token_cache.insert(token, validated_token.clone(), jwk, expires_at);
+            }
+        }
+
+        Ok(validated_token)
+    }
+}
+
+fn kid(header: &Header) -> Result<&str, Error> {
+    Ok(some_or_return_error!(
+        header.kid.as_deref(),
+        MALFORMED_TOKEN,
+        "'kid' is missing from header"
+    ))
+}
+
+// only uses the keys known by now, an unknown 'kid' merely scheduling a refresh of the keys
+impl TokenValidator for JwtTokenValidator {
+    fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let header = self.checked_header(token)?;
+        let jwk = match self.jwks_provider(token, &header)? {
+            Some(jwks_provider) => jwks_provider.find(kid(&header)?),
+            None => None,
+        };
+
+        if let Some(validated_token) = self.cached_token(token, jwk.as_ref()) {
+            return Ok(validated_token);
+        }
+
+        self.validate_with_key(token, header, jwk)
     }
 }
 
+// waits for the refresh of the keys once a token references an unknown 'kid'
+#[async_trait]
+impl AsyncTokenValidator for JwtTokenValidator {
+    async fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        let header = self.checked_header(token)?;
+        let jwk = match self.jwks_provider(token, &header)? {
+            Some(jwks_provider) => jwks_provider.find_or_refresh(kid(&header)?).await,
+            None => None,
+        };
+
+        if let Some(validated_token) = self.cached_token(token, jwk.as_ref()) {
+            return Ok(validated_token);
+        }
+
+        self.validate_with_key(token, header, jwk)
+    }
+}
+
+// the decoding key is rebuilt whenever the provider returns a different key for the 'kid'
+fn cached_decoding_key(
+    decoding_keys: &Mutex<HashMap<(String, Algorithm), CachedDecodingKey>>,
+    kid: String,
+    jwk: Jwk,
+    header_algorithm: Algorithm,
+) -> Result<(DecodingKey, Algorithm), Error> {
+    let cache_key = (kid, header_algorithm);
+
+    if let Ok(decoding_keys) = decoding_keys.lock() {
+        if let Some((cached_jwk, decoding_key, algorithm)) = decoding_keys.get(&cache_key) {
+            if *cached_jwk == jwk {
+                return Ok((decoding_key.clone(), *algorithm));
+            }
+        }
+    }
+
+    let (decoding_key, algorithm) = decoding_key_for_jwk(&jwk, header_algorithm)?;
+
+    if let Ok(mut decoding_keys) = decoding_keys.lock() {
+        if decoding_keys.len() >= MAX_DECODING_KEYS {
+            decoding_keys.clear();
+        }
+
+        decoding_keys.insert(cache_key, (jwk, decoding_key.clone(), algorithm));
+    }
+
+    Ok((decoding_key, algorithm))
+}
+
+///
+/// Builds the decoding key for the specified public `jwk` along with the algorithm
+/// the token must have been signed with.
+///
+/// # Arguments
+///
+/// * `jwk` - Public key. Either RSA, EC (P-256 or P-384) or OKP (Ed25519).
+/// * `header_algorithm` - Algorithm specified within the token's header, used for RSA keys
+///   which do not specify their algorithm.
+///
+/// # Returns
+///
+/// * `Ok` - Decoding key and algorithm.
+/// * `Err` - Error with the kind `MALFORMED_TOKEN` if the key is not supported.
+pub(crate) fn decoding_key_for_jwk(
+    jwk: &Jwk,
+    header_algorithm: Algorithm,
+) -> Result<(DecodingKey, Algorithm), Error> {
+    let (decoding_key, inferred_algorithm) = match jwk.algorithm {
+        AlgorithmParameters::RSA(ref rsa) => {
+            let decoding_key = ok_or_return_error!(
+                DecodingKey::from_rsa_components(&rsa.n, &rsa.e),
+                MALFORMED_TOKEN,
+                "failed to get decoding key: "
+            );
+
+            let algorithm = if RSA_ALGORITHMS.contains(&header_algorithm) {
+                Some(header_algorithm)
+            } else {
+                None
+            };
+
+            (decoding_key, algorithm)
+        }
+        AlgorithmParameters::EllipticCurve(ref ec) => {
+            let algorithm = match ec.curve {
+                EllipticCurve::P256 => Algorithm::ES256,
+                EllipticCurve::P384 => Algorithm::ES384,
+                _ => {
+                    return Err(Error::new(
+                        MALFORMED_TOKEN,
+                        format!("got an unsupported curve for the 'jwk': {:?}", ec.curve),
+                    ))
+                }
+            };
+
+            let decoding_key = ok_or_return_error!(
+                DecodingKey::from_ec_components(&ec.x, &ec.y),
+                MALFORMED_TOKEN,
+                "failed to get decoding key: "
+            );
+
+            (decoding_key, Some(algorithm))
+        }
+        AlgorithmParameters::OctetKeyPair(ref okp) => {
+            if okp.curve != EllipticCurve::Ed25519 {
+                return Err(Error::new(
+                    MALFORMED_TOKEN,
+                    format!("got an unsupported curve for the 'jwk': {:?}", okp.curve),
+                ));
+            }
+
+            let decoding_key = ok_or_return_error!(
+                DecodingKey::from_ed_components(&okp.x),
+                MALFORMED_TOKEN,
+                "failed to get decoding key: "
+            );
+
+            (decoding_key, Some(Algorithm::EdDSA))
+        }
+        AlgorithmParameters::OctetKey(_) => {
+            return Err(Error::new(
+                MALFORMED_TOKEN,
+                "symmetric keys are not accepted within a 'jwk_set'",
+            ));
+        }
+    };
+
+    let algorithm = match jwk.common.key_algorithm {
+        Some(key_algorithm) => ok_or_return_error!(
+            Algorithm::from_str(key_algorithm.to_string().as_str()),
+            MALFORMED_TOKEN,
+            "failed to get algorithm for 'key_algorithm': "
+        ),
+        None => some_or_return_error!(
+            inferred_algorithm,
+            MALFORMED_TOKEN,
+            "'jwk' is missing the 'key_algorithm'"
+        ),
+    };
+
+    Ok((decoding_key, algorithm))
+}
+
 /// Tries to get a `JwkSet` by reaching to the specified `jwks_uri`.
 ///
 /// # Arguments
@@ -119,66 +703,47 @@ pub async fn try_get_jwks(jwks_uri: &str) -> Result<JwkSet, Error> {
 
 #[cfg(test)]
 pub mod tests {
-    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
 
-    use crate::auth::error_kind::{INVALID_TOKEN, MALFORMED_TOKEN};
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use jsonwebtoken::jwk::JwkSet;
+    use jsonwebtoken::{encode, get_current_timestamp, Algorithm, EncodingKey, Header};
+    use mockall::predicate::eq;
+    use reqwest::Client;
+    use serde_json::{json, Value};
+
+    use crate::auth::error_kind::{
+        DISCOVERY_FAILURE, INVALID_ALGORITHM, INVALID_TOKEN, MALFORMED_TOKEN,
+    };
+    use crate::auth::jwks_provider::MockJwksProvider;
+    use crate::auth::jwt_issuer::{JwtIssuer, SigningKey};
     use crate::auth::jwt_token_validator::{try_get_jwks, JwtTokenValidator};
+    use crate::auth::jwt_validation_options::JwtValidationOptions;
+    use crate::auth::openid_discovery::OPENID_CONFIGURATION_PATH;
+    use crate::auth::remote_jwks_provider::RemoteJwksOptions;
     use crate::auth::token_validator::TokenValidator;
-    use crate::config_reader::ConfigReader;
-    use crate::secrets::get_secrets_manager;
+    use crate::error::Error;
+    use crate::secrets::secrets_manager::SecretsManager;
     use crate::test_base::get_unit_test_data_path;
 
     #[tokio::test]
     pub async fn validate_expired_jwt_token_returns_error() {
-        let config_reader = ConfigReader::default();
-        let config_path = get_config_file();
-        let config_value = config_reader
-            .read(config_path)
-            .expect("expected config reader");
-        let token_validator_config = config_value
-            .get("TokenValidator")
-            .expect("expected 'TokenValidator'");
-        let jwks_uri = token_validator_config
-            .get("JwksUri")
-            .expect("expected 'JwksUri'")
-            .as_str()
-            .expect("expected 'JwksUri' as string");
-        let jwks = try_get_jwks(jwks_uri).await.expect("expected 'JwkSet'");
-        let issuers: Vec<String> = token_validator_config
-            .get("Issuers")
-            .expect("expected 'Issuers'")
-            .as_sequence()
-            .expect("expected 'Issuers' as sequence")
-            .iter()
-            .map(|v| {
-                v.as_str()
-                    .expect("expected 'Issuers' value as string")
-                    .to_string()
-            })
-            .collect();
-        let audience: Vec<String> = token_validator_config
-            .get("Audience")
-            .expect("expected 'Audience'")
-            .as_sequence()
-            .expect("expected 'Audience' as sequence")
-            .iter()
-            .map(|v| {
-                v.as_str()
-                    .expect("expected 'Audience' value as string")
-                    .to_string()
-            })
-            .collect();
-        let secrets_manager = get_secrets_manager().expect("failed to get secrets manager");
-        let expired_token = secrets_manager
-            .get_secret(
-                config_value
-                    .get("ExpiredTokenSecret")
-                    .expect("expected 'ExpiredTokenSecret'")
-                    .as_str()
-                    .expect("expected 'ExpiredTokenSecret' as string"),
-            )
-            .expect("failed to get expired token from secrets manager");
-        let token_validator = JwtTokenValidator::new(jwks, issuers, audience);
+        let issuer = get_issuer();
+        let jwks_uri = start_jwks_server(&issuer).await;
+        let jwks = try_get_jwks(jwks_uri.as_str())
+            .await
+            .expect("expected 'JwkSet'");
+        let expired_token = issuer
+            .token()
+            .subject("user-1")
+            .expired()
+            .sign()
+            .expect("expected expired token");
+        let token_validator =
+            JwtTokenValidator::new(jwks, vec![ISSUER.to_string()], vec![AUDIENCE.to_string()]);
 
         let error = token_validator
             .validate(expired_token.as_str())
@@ -189,30 +754,663 @@ pub mod tests {
     }
 
     #[tokio::test]
-    pub async fn try_get_jwks_succeeds_for_example_uri() {
-        let config_reader = ConfigReader::default();
-        let config_path = get_config_file();
-        let config_value = config_reader
-            .read(config_path)
-            .expect("expected config reader");
-        let token_validator_config = config_value
-            .get("TokenValidator")
-            .expect("expected 'TokenValidator'");
-        let jwks_uri = token_validator_config
-            .get("JwksUri")
-            .expect("expected 'JwksUri'")
-            .as_str()
-            .expect("expected 'JwksUri' as string");
-
-        let result = try_get_jwks(jwks_uri).await;
+    pub async fn try_get_jwks_succeeds_for_local_uri() {
+        let issuer = get_issuer();
+        let jwks_uri = start_jwks_server(&issuer).await;
+
+        let result = try_get_jwks(jwks_uri.as_str()).await;
+
+        assert_eq!(issuer.jwk_set(), result.expect("expected 'JwkSet'"));
+    }
+
+    #[test]
+    pub fn validate_token_with_unknown_kid_returns_error() {
+        let mut jwks_provider = MockJwksProvider::new();
+        jwks_provider
+            .expect_find()
+            .with(eq("unknown"))
+            .times(1)
+            .returning(|_| None);
+        let header = Header {
+            kid: Some("unknown".to_string()),
+            ..Header::default()
+        };
+        let token = encode(
+            &header,
+            &json!({ "sub": "user-1" }),
+            &EncodingKey::from_secret(b"secret"),
+        )
+        .expect("expected token");
+        let token_validator =
+            JwtTokenValidator::with_jwks_provider(Arc::new(jwks_provider), vec![], vec![]);
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected validation failure due to unknown 'kid'");
+
+        assert_eq!(MALFORMED_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn validate_es256_token_succeeds() {
+        let token = encode(
+            &get_header(Algorithm::ES256, "es256"),
+            &get_claims(),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        let result = get_jwks_token_validator().validate(token.as_str());
+
+        assert_eq!(
+            Some("user-1".to_string()),
+            result.expect("expected valid token").subject()
+        );
+    }
+
+    #[test]
+    pub fn validate_es384_token_succeeds() {
+        let token = encode(
+            &get_header(Algorithm::ES384, "es384"),
+            &get_claims(),
+            &EncodingKey::from_ec_pem(read_test_file("es384_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        let result = get_jwks_token_validator().validate(token.as_str());
 
         assert!(result.is_ok());
     }
 
-    fn get_config_file() -> PathBuf {
-        let mut config_path = get_unit_test_data_path(file!());
-        config_path.push("config.yaml");
+    #[test]
+    pub fn validate_eddsa_token_succeeds() {
+        let token = encode(
+            &get_header(Algorithm::EdDSA, "eddsa"),
+            &get_claims(),
+            &EncodingKey::from_ed_pem(read_test_file("eddsa_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        let result = get_jwks_token_validator().validate(token.as_str());
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    pub fn validate_token_with_algorithm_not_matching_key_returns_error() {
+        let token = encode(
+            &get_header(Algorithm::ES256, "es384"),
+            &get_claims(),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        let error = get_jwks_token_validator()
+            .validate(token.as_str())
+            .expect_err("expected algorithm mismatch");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn validate_hmac_token_succeeds() {
+        let token_validator = JwtTokenValidator::with_secret(
+            b"shared secret",
+            vec![Algorithm::HS256, Algorithm::HS512],
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        )
+        .expect("expected token validator");
+        let token = encode(
+            &Header::new(Algorithm::HS512),
+            &get_claims(),
+            &EncodingKey::from_secret(b"shared secret"),
+        )
+        .expect("expected token");
+
+        let result = token_validator.validate(token.as_str());
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    pub fn validate_hmac_token_with_wrong_secret_returns_error() {
+        let token_validator = JwtTokenValidator::from_secrets_manager(
+            &StaticSecretsManager {},
+            "shared-secret-id",
+            vec![Algorithm::HS256],
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        )
+        .expect("expected token validator");
+        let token = encode(
+            &Header::new(Algorithm::HS256),
+            &get_claims(),
+            &EncodingKey::from_secret(b"another secret"),
+        )
+        .expect("expected token");
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected invalid signature");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn with_secret_non_hmac_algorithm_returns_error() {
+        let result =
+            JwtTokenValidator::with_secret(b"secret", vec![Algorithm::RS256], vec![], vec![]);
+
+        assert_eq!(
+            INVALID_ALGORITHM,
+            result
+                .err()
+                .expect("expected invalid algorithm")
+                .error_kind()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn from_discovery_accepts_tokens_of_every_issuer() {
+        let jwk_set = read_jwks();
+        let first_issuer = start_discovery_server(
+            json!({ "keys": [jwk_set["keys"][0]] }),
+            json!(["ES256", "EdDSA"]),
+        )
+        .await;
+        let second_issuer =
+            start_discovery_server(json!({ "keys": [jwk_set["keys"][2]] }), json!(["EdDSA"])).await;
+        let token_validator = JwtTokenValidator::from_discovery(
+            &[first_issuer.as_str(), second_issuer.as_str()],
+            vec![AUDIENCE.to_string()],
+            Client::new(),
+            RemoteJwksOptions::default(),
+        )
+        .await
+        .expect("expected token validator");
+        let first_token = encode(
+            &get_header(Algorithm::ES256, "es256"),
+            &get_claims_for_issuer(first_issuer.as_str()),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+        let second_token = encode(
+            &get_header(Algorithm::EdDSA, "eddsa"),
+            &get_claims_for_issuer(second_issuer.as_str()),
+            &EncodingKey::from_ed_pem(read_test_file("eddsa_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+        let unknown_issuer_token = encode(
+            &get_header(Algorithm::EdDSA, "eddsa"),
+            &get_claims_for_issuer(ISSUER),
+            &EncodingKey::from_ed_pem(read_test_file("eddsa_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        assert!(token_validator.validate(first_token.as_str()).is_ok());
+        assert!(token_validator.validate(second_token.as_str()).is_ok());
+        assert!(token_validator
+            .validate(unknown_issuer_token.as_str())
+            .is_err());
+    }
+
+    #[tokio::test]
+    pub async fn from_discovery_rejects_token_signed_with_key_of_another_issuer() {
+        let jwk_set = read_jwks();
+        let first_issuer =
+            start_discovery_server(json!({ "keys": [jwk_set["keys"][0]] }), json!(["ES256"])).await;
+        let second_issuer =
+            start_discovery_server(json!({ "keys": [jwk_set["keys"][2]] }), json!(["EdDSA"])).await;
+        let token_validator = JwtTokenValidator::from_discovery(
+            &[first_issuer.as_str(), second_issuer.as_str()],
+            vec![AUDIENCE.to_string()],
+            Client::new(),
+            RemoteJwksOptions::default(),
+        )
+        .await
+        .expect("expected token validator");
+        let token = encode(
+            &get_header(Algorithm::ES256, "es256"),
+            &get_claims_for_issuer(second_issuer.as_str()),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        let error = crate::auth::async_token_validator::AsyncTokenValidator::validate(
+            &token_validator,
+            token.as_str(),
+        )
+        .await
+        .expect_err("expected key of the first issuer not to be accepted");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn from_discovery_rejects_unsupported_algorithm() {
+        let jwk_set = read_jwks();
+        let issuer =
+            start_discovery_server(json!({ "keys": [jwk_set["keys"][0]] }), json!(["RS256"])).await;
+        let token_validator = JwtTokenValidator::from_discovery(
+            &[issuer.as_str()],
+            vec![AUDIENCE.to_string()],
+            Client::new(),
+            RemoteJwksOptions::default(),
+        )
+        .await
+        .expect("expected token validator");
+        let token = encode(
+            &get_header(Algorithm::ES256, "es256"),
+            &get_claims_for_issuer(issuer.as_str()),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected algorithm not to be allowed");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn from_discovery_without_supported_algorithm_returns_error() {
+        let jwk_set = read_jwks();
+        let issuer = start_discovery_server(
+            json!({ "keys": [jwk_set["keys"][0]] }),
+            json!(["none", "HS256"]),
+        )
+        .await;
+
+        let result = JwtTokenValidator::from_discovery(
+            &[issuer.as_str()],
+            vec![AUDIENCE.to_string()],
+            Client::new(),
+            RemoteJwksOptions::default(),
+        )
+        .await;
+
+        assert_eq!(
+            DISCOVERY_FAILURE,
+            result
+                .err()
+                .expect("expected no supported algorithm")
+                .error_kind()
+        );
+    }
+
+    #[test]
+    pub fn validate_with_options_applies_leeway_to_expiration() {
+        let mut claims = get_claims();
+        claims["exp"] = json!(get_current_timestamp() - 30);
+        let token = encode_hmac_token(&Header::new(Algorithm::HS256), &claims);
+
+        let strict_token_validator = get_hmac_token_validator()
+            .with_options(JwtValidationOptions::default().with_leeway(Duration::ZERO));
+        let lenient_token_validator = get_hmac_token_validator()
+            .with_options(JwtValidationOptions::default().with_leeway(Duration::from_secs(60)));
+
+        assert!(strict_token_validator.validate(token.as_str()).is_err());
+        assert!(lenient_token_validator.validate(token.as_str()).is_ok());
+    }
+
+    #[test]
+    pub fn validate_with_nbf_validation_rejects_token_not_yet_valid() {
+        let mut claims = get_claims();
+        claims["nbf"] = json!(get_current_timestamp() + 600);
+        let token = encode_hmac_token(&Header::new(Algorithm::HS256), &claims);
+        let token_validator = get_hmac_token_validator()
+            .with_options(JwtValidationOptions::default().with_nbf_validation(true));
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected token not to be valid yet");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn validate_with_required_claims_rejects_token_missing_claim() {
+        let token = encode_hmac_token(&Header::new(Algorithm::HS256), &get_claims());
+        let token_validator = get_hmac_token_validator()
+            .with_options(JwtValidationOptions::default().with_required_claims(["sub", "jti"]));
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected missing 'jti' claim");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+        assert!(error.message().contains("jti"));
+    }
+
+    #[test]
+    pub fn validate_with_max_age_rejects_old_token() {
+        let mut claims = get_claims();
+        claims["iat"] = json!(get_current_timestamp() - 3600);
+        let token = encode_hmac_token(&Header::new(Algorithm::HS256), &claims);
+        let token_validator = get_hmac_token_validator().with_options(
+            JwtValidationOptions::default()
+                .with_leeway(Duration::ZERO)
+                .with_max_age(Duration::from_secs(600)),
+        );
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected token to be too old");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    #[test]
+    pub fn validate_with_token_cache_returns_cached_token() {
+        let token = encode(
+            &get_header(Algorithm::ES256, "es256"),
+            &get_claims(),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+        let token_validator = get_jwks_token_validator().with_token_cache(10);
+
+        let first = token_validator
+            .validate(token.as_str())
+            .expect("expected valid token");
+        let second = token_validator
+            .validate(token.as_str())
+            .expect("expected cached token");
+        let tampered = token_validator.validate(format!("{}x", token).as_str());
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(tampered.is_err());
+    }
+
+    #[test]
+    pub fn validate_with_token_cache_rejects_cached_token_of_removed_kid() {
+        let issuer = get_rotating_issuer();
+        let jwk = issuer.jwk_set().keys[0].clone();
+        let calls = AtomicUsize::new(0);
+        let mut jwks_provider = MockJwksProvider::new();
+        jwks_provider
+            .expect_find()
+            .with(eq("rotating"))
+            .returning(move |_| match calls.fetch_add(1, Ordering::SeqCst) {
+                0 | 1 => Some(jwk.clone()),
+                _ => None,
+            });
+        let token_validator = JwtTokenValidator::with_jwks_provider(
+            Arc::new(jwks_provider),
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        )
+        .with_token_cache(10);
+        let token = issuer.token().sign().expect("expected signed token");
+
+        let first = token_validator
+            .validate(token.as_str())
+            .expect("expected valid token");
+        let second = token_validator
+            .validate(token.as_str())
+            .expect("expected cached token");
+        let removed = token_validator.validate(token.as_str());
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(removed.is_err());
+    }
+
+    #[test]
+    pub fn validate_with_token_cache_rejects_cached_token_of_rotated_kid() {
+        let first_issuer = get_rotating_issuer();
+        let second_issuer = get_rotating_issuer();
+        let jwks = [
+            first_issuer.jwk_set().keys[0].clone(),
+            second_issuer.jwk_set().keys[0].clone(),
+        ];
+        let calls = AtomicUsize::new(0);
+        let mut jwks_provider = MockJwksProvider::new();
+        jwks_provider
+            .expect_find()
+            .with(eq("rotating"))
+            .returning(move |_| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                Some(jwks[call.min(1)].clone())
+            });
+        let token_validator = JwtTokenValidator::with_jwks_provider(
+            Arc::new(jwks_provider),
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        )
+        .with_token_cache(10);
+        let old_token = first_issuer.token().sign().expect("expected signed token");
+        let new_token = second_issuer.token().sign().expect("expected signed token");
+
+        let old = token_validator.validate(old_token.as_str());
+        let new = token_validator.validate(new_token.as_str());
+        let rotated = token_validator.validate(old_token.as_str());
+
+        assert!(old.is_ok());
+        assert!(new.is_ok());
+        assert!(rotated.is_err());
+    }
+
+    #[test]
+    pub fn validate_rebuilds_decoding_key_of_rotated_kid() {
+        let first_issuer = get_rotating_issuer();
+        let second_issuer = get_rotating_issuer();
+        let jwks = [
+            first_issuer.jwk_set().keys[0].clone(),
+            second_issuer.jwk_set().keys[0].clone(),
+        ];
+        let calls = AtomicUsize::new(0);
+        let mut jwks_provider = MockJwksProvider::new();
+        jwks_provider
+            .expect_find()
+            .with(eq("rotating"))
+            .returning(move |_| {
+                let call = calls.fetch_add(1, Ordering::SeqCst);
+                Some(jwks[call.min(1)].clone())
+            });
+        let token_validator = JwtTokenValidator::with_jwks_provider(
+            Arc::new(jwks_provider),
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        );
+
+        for issuer in [first_issuer, second_issuer] {
+            let token = issuer.token().sign().expect("expected signed token");
+
+            assert!(token_validator.validate(token.as_str()).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    pub async fn async_validate_waits_for_key_of_unknown_kid() {
+        let issuer = get_rotating_issuer();
+        let jwk = issuer.jwk_set().keys[0].clone();
+        let mut jwks_provider = MockJwksProvider::new();
+        jwks_provider.expect_find().times(0);
+        jwks_provider
+            .expect_find_or_refresh()
+            .with(eq("rotating"))
+            .times(1)
+            .returning(move |_| Some(jwk.clone()));
+        let token_validator = JwtTokenValidator::with_jwks_provider(
+            Arc::new(jwks_provider),
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        );
+        let token = issuer.token().sign().expect("expected signed token");
+
+        let result = crate::auth::async_token_validator::AsyncTokenValidator::validate(
+            &token_validator,
+            token.as_str(),
+        )
+        .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    pub fn validate_with_token_type_checks_typ_header() {
+        let access_token = encode_hmac_token(
+            &Header {
+                typ: Some("at+jwt".to_string()),
+                ..Header::new(Algorithm::HS256)
+            },
+            &get_claims(),
+        );
+        let id_token = encode_hmac_token(&Header::new(Algorithm::HS256), &get_claims());
+        let token_validator = get_hmac_token_validator()
+            .with_options(JwtValidationOptions::default().with_token_type("application/at+jwt"));
+
+        assert!(token_validator.validate(access_token.as_str()).is_ok());
+        assert!(token_validator.validate(id_token.as_str()).is_err());
+    }
+
+    #[test]
+    pub fn validate_with_algorithms_rejects_algorithm_not_allowed() {
+        let token = encode(
+            &get_header(Algorithm::ES256, "es256"),
+            &get_claims(),
+            &EncodingKey::from_ec_pem(read_test_file("es256_private_key.pem").as_slice())
+                .expect("expected encoding key"),
+        )
+        .expect("expected token");
+        let token_validator = get_jwks_token_validator()
+            .with_options(JwtValidationOptions::default().with_algorithms(vec![Algorithm::EdDSA]));
+
+        let error = token_validator
+            .validate(token.as_str())
+            .expect_err("expected algorithm not to be allowed");
+
+        assert_eq!(INVALID_TOKEN, error.error_kind());
+    }
+
+    const ISSUER: &str = "https://issuer.example/";
+    const AUDIENCE: &str = "cp-core";
+
+    struct StaticSecretsManager {}
+
+    impl SecretsManager for StaticSecretsManager {
+        fn get_secret(&self, _: &str) -> Result<String, Error> {
+            Ok("shared secret".to_string())
+        }
+    }
+
+    fn get_hmac_token_validator() -> JwtTokenValidator {
+        JwtTokenValidator::with_secret(
+            b"shared secret",
+            vec![Algorithm::HS256],
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        )
+        .expect("expected token validator")
+    }
+
+    fn encode_hmac_token(header: &Header, claims: &Value) -> String {
+        encode(header, claims, &EncodingKey::from_secret(b"shared secret")).expect("expected token")
+    }
+
+    fn get_jwks_token_validator() -> JwtTokenValidator {
+        let jwk_set: JwkSet = serde_json::from_slice(read_test_file("jwks.json").as_slice())
+            .expect("expected 'JwkSet'");
+
+        JwtTokenValidator::new(
+            jwk_set,
+            vec![ISSUER.to_string()],
+            vec![AUDIENCE.to_string()],
+        )
+    }
+
+    fn get_header(algorithm: Algorithm, kid: &str) -> Header {
+        Header {
+            kid: Some(kid.to_string()),
+            ..Header::new(algorithm)
+        }
+    }
+
+    fn get_claims() -> Value {
+        get_claims_for_issuer(ISSUER)
+    }
+
+    fn get_claims_for_issuer(issuer: &str) -> Value {
+        let now = get_current_timestamp();
+
+        json!({
+            "sub": "user-1",
+            "iss": issuer,
+            "aud": AUDIENCE,
+            "iat": now,
+            "exp": now + 3600
+        })
+    }
+
+    fn read_jwks() -> Value {
+        serde_json::from_slice(read_test_file("jwks.json").as_slice()).expect("expected 'JwkSet'")
+    }
+
+    fn get_issuer() -> JwtIssuer {
+        JwtIssuer::new(ISSUER)
+            .with_audience(AUDIENCE)
+            .with_signing_key(
+                SigningKey::generate("es256", Algorithm::ES256).expect("expected signing key"),
+            )
+    }
+
+    fn get_rotating_issuer() -> JwtIssuer {
+        JwtIssuer::new(ISSUER)
+            .with_audience(AUDIENCE)
+            .with_signing_key(
+                SigningKey::generate("rotating", Algorithm::ES256).expect("expected signing key"),
+            )
+    }
+
+    async fn start_jwks_server(issuer: &JwtIssuer) -> String {
+        let jwk_set = serde_json::to_value(issuer.jwk_set()).expect("expected 'JwkSet' as JSON");
+
+        format!(
+            "{}/jwks.json",
+            start_discovery_server(jwk_set, json!(["ES256"])).await
+        )
+    }
+
+    async fn start_discovery_server(jwk_set: Value, algorithms: Value) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("expected listener");
+        let issuer = format!(
+            "http://{}",
+            listener.local_addr().expect("expected local address")
+        );
+        let configuration = json!({
+            "issuer": issuer,
+            "jwks_uri": format!("{}/jwks.json", issuer),
+            "id_token_signing_alg_values_supported": algorithms
+        });
+        let router = Router::new()
+            .route(
+                OPENID_CONFIGURATION_PATH,
+                get(move || async move { Json(configuration) }),
+            )
+            .route("/jwks.json", get(move || async move { Json(jwk_set) }));
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        issuer
+    }
+
+    fn read_test_file(file_name: &str) -> Vec<u8> {
+        let mut path = get_unit_test_data_path(file!());
+        path.push(file_name);
 
-        config_path
+        std::fs::read(path).expect("expected test file")
     }
 }
@@ -0,0 +1,187 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::time::Duration;
+
+use jsonwebtoken::Algorithm;
+
+use crate::auth::token::EXPIRATION_CLAIM;
+
+const DEFAULT_LEEWAY_IN_SECONDS: u64 = 60;
+
+/// `JwtValidationOptions` holds the rules applied by `JwtTokenValidator` on top of the
+/// signature, issuer and audience checks.
+///
+/// By default the expiration is validated with a leeway of 60 seconds and the `exp` claim
+/// is required.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JwtValidationOptions {
+    leeway: Duration,
+    validate_nbf: bool,
+    required_claims: Vec<String>,
+    max_age: Option<Duration>,
+    algorithms: Vec<Algorithm>,
+    token_type: Option<String>,
+}
+
+impl Default for JwtValidationOptions {
+    fn default() -> Self {
+        Self {
+            leeway: Duration::from_secs(DEFAULT_LEEWAY_IN_SECONDS),
+            validate_nbf: false,
+            required_claims: vec![EXPIRATION_CLAIM.to_string()],
+            max_age: None,
+            algorithms: Vec::new(),
+            token_type: None,
+        }
+    }
+}
+
+impl JwtValidationOptions {
+    /// Clock skew tolerated when validating `exp`, `nbf` and `iat`.
+    pub fn with_leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    /// Rejects tokens used before their `nbf` claim.
+    pub fn with_nbf_validation(mut self, validate_nbf: bool) -> Self {
+        self.validate_nbf = validate_nbf;
+        self
+    }
+
+    /// Claims which must be present within the token, on top of the ones already required,
+    /// i.e. `exp`.
+    pub fn with_required_claims<T: Into<String>>(
+        mut self,
+        required_claims: impl IntoIterator<Item = T>,
+    ) -> Self {
+        for required_claim in required_claims.into_iter().map(Into::into) {
+            if !self.required_claims.contains(&required_claim) {
+                self.required_claims.push(required_claim);
+            }
+        }
+
+        self
+    }
+
+    /// Accepts tokens without the `exp` claim, which then never expire. Only meant for tokens
+    /// whose lifetime is bounded otherwise, i.e. through `with_max_age`.
+    pub fn without_required_expiration(mut self) -> Self {
+        self.required_claims
+            .retain(|required_claim| required_claim != EXPIRATION_CLAIM);
+        self
+    }
+
+    /// Rejects tokens whose `iat` claim is older than `max_age`. Makes `iat` mandatory.
+    pub fn with_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Only accepts tokens signed with one of `algorithms`, regardless of the keys.
+    pub fn with_algorithms(mut self, algorithms: Vec<Algorithm>) -> Self {
+        self.algorithms = algorithms;
+        self
+    }
+
+    /// Requires the `typ` header to match `token_type`, i.e. `at+jwt` as per RFC 9068.
+    /// The `application/` prefix is ignored and the comparison is case-insensitive.
+    pub fn with_token_type(mut self, token_type: impl Into<String>) -> Self {
+        self.token_type = Some(token_type.into());
+        self
+    }
+
+    pub fn leeway(&self) -> Duration {
+        self.leeway
+    }
+
+    pub fn validate_nbf(&self) -> bool {
+        self.validate_nbf
+    }
+
+    pub fn required_claims(&self) -> &[String] {
+        self.required_claims.as_slice()
+    }
+
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
+
+    pub fn algorithms(&self) -> &[Algorithm] {
+        self.algorithms.as_slice()
+    }
+
+    pub fn token_type(&self) -> Option<&str> {
+        self.token_type.as_deref()
+    }
+
+    /// Whether `algorithm` is accepted. Every algorithm is accepted if no allow-list was set.
+    pub fn is_algorithm_allowed(&self, algorithm: &Algorithm) -> bool {
+        self.algorithms.is_empty() || self.algorithms.contains(algorithm)
+    }
+
+    /// Whether the `typ` header fulfills the expected token type, if any.
+    pub fn is_token_type_allowed(&self, token_type: Option<&str>) -> bool {
+        match self.token_type {
+            Some(ref expected) => match token_type {
+                Some(token_type) => {
+                    normalize_token_type(token_type) == normalize_token_type(expected)
+                }
+                None => false,
+            },
+            None => true,
+        }
+    }
+}
+
+fn normalize_token_type(token_type: &str) -> String {
+    let token_type = token_type.to_ascii_lowercase();
+
+    match token_type.strip_prefix("application/") {
+        Some(token_type) => token_type.to_string(),
+        None => token_type,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use jsonwebtoken::Algorithm;
+
+    use crate::auth::jwt_validation_options::JwtValidationOptions;
+
+    #[test]
+    pub fn is_algorithm_allowed_without_allow_list_accepts_any() {
+        let options = JwtValidationOptions::default();
+
+        assert!(options.is_algorithm_allowed(&Algorithm::RS256));
+    }
+
+    #[test]
+    pub fn is_algorithm_allowed_with_allow_list_accepts_listed_only() {
+        let options = JwtValidationOptions::default().with_algorithms(vec![Algorithm::ES256]);
+
+        assert!(options.is_algorithm_allowed(&Algorithm::ES256));
+        assert!(!options.is_algorithm_allowed(&Algorithm::RS256));
+    }
+
+    #[test]
+    pub fn with_required_claims_keeps_expiration_required() {
+        let options = JwtValidationOptions::default().with_required_claims(["sub", "exp"]);
+        let without_expiration = options.clone().without_required_expiration();
+
+        assert_eq!(["exp", "sub"], options.required_claims());
+        assert_eq!(["sub"], without_expiration.required_claims());
+    }
+
+    #[test]
+    pub fn is_token_type_allowed_normalizes_media_type() {
+        let options = JwtValidationOptions::default().with_token_type("at+jwt");
+
+        assert!(options.is_token_type_allowed(Some("application/AT+JWT")));
+        assert!(options.is_token_type_allowed(Some("at+jwt")));
+        assert!(!options.is_token_type_allowed(Some("JWT")));
+        assert!(!options.is_token_type_allowed(None));
+    }
+}
@@ -2,9 +2,29 @@
  * Copyright (c) Gabriel Amihalachioaie, SimpleG 2023.
  */
 
+pub mod api_key_validator;
+pub mod async_token_validator;
+pub mod audit;
+pub mod authenticated;
+pub mod authentication_layer;
 pub mod authorization;
+pub mod chained_token_validator;
+pub mod claims_token;
+pub mod client_certificate;
+pub mod composite_token_validator;
+pub mod credentials;
+pub mod dpop;
 pub mod error_kind;
-mod jwt_token;
+pub mod introspection_token_validator;
+pub mod jwks_provider;
+pub mod jwt_issuer;
+pub(crate) mod jwt_token;
 pub mod jwt_token_validator;
+pub mod jwt_validation_options;
+pub mod openid_discovery;
+pub mod policy;
+pub mod remote_jwks_provider;
+pub mod revocation_store;
 pub mod token;
+pub mod token_cache;
 pub mod token_validator;
@@ -0,0 +1,159 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::str::FromStr;
+
+use jsonwebtoken::Algorithm;
+use reqwest::Client;
+use serde::Deserialize;
+
+use crate::auth::error_kind::DISCOVERY_FAILURE;
+use crate::error::Error;
+use crate::ok_or_return_error;
+
+pub const OPENID_CONFIGURATION_PATH: &str = "/.well-known/openid-configuration";
+
+/// Subset of the OpenID Provider metadata needed for validating the tokens it issues.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OpenIdConfiguration {
+    pub issuer: String,
+    pub jwks_uri: String,
+    #[serde(default)]
+    pub id_token_signing_alg_values_supported: Vec<String>,
+}
+
+impl OpenIdConfiguration {
+    /// Supported signing algorithms, skipping those which are unknown, i.e. `none`.
+    pub fn algorithms(&self) -> Vec<Algorithm> {
+        self.id_token_signing_alg_values_supported
+            .iter()
+            .filter_map(|algorithm| Algorithm::from_str(algorithm).ok())
+            .collect()
+    }
+}
+
+///
+/// Tries to get the OpenID Provider metadata of `issuer_url` by reaching to its
+/// `/.well-known/openid-configuration` document.
+///
+/// # Arguments
+///
+/// * `client` - HTTP client used for the request.
+/// * `issuer_url` - Issuer whose metadata is retrieved, i.e. `https://simpleg.eu.auth0.com/`.
+///
+/// # Returns
+///
+/// * `Ok` - OpenID Provider metadata.
+/// * `Err` - Error with the kind `DISCOVERY_FAILURE` if the document could not be retrieved
+///   or its `issuer` does not match `issuer_url`.
+pub async fn try_get_openid_configuration(
+    client: &Client,
+    issuer_url: &str,
+) -> Result<OpenIdConfiguration, Error> {
+    let discovery_url = format!(
+        "{}{}",
+        issuer_url.trim_end_matches('/'),
+        OPENID_CONFIGURATION_PATH
+    );
+
+    let response = ok_or_return_error!(
+        client.get(discovery_url.as_str()).send().await,
+        DISCOVERY_FAILURE,
+        "failed to request the OpenID configuration: "
+    );
+
+    let response = ok_or_return_error!(
+        response.error_for_status(),
+        DISCOVERY_FAILURE,
+        "invalid response obtained getting the OpenID configuration: "
+    );
+
+    let configuration = ok_or_return_error!(
+        response.json::<OpenIdConfiguration>().await,
+        DISCOVERY_FAILURE,
+        "response cannot be deserialized as an OpenID configuration: "
+    );
+
+    if configuration.issuer.trim_end_matches('/') != issuer_url.trim_end_matches('/') {
+        return Err(Error::new(
+            DISCOVERY_FAILURE,
+            format!(
+                "OpenID configuration issuer '{}' does not match '{}'",
+                configuration.issuer, issuer_url
+            ),
+        ));
+    }
+
+    Ok(configuration)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use jsonwebtoken::Algorithm;
+    use reqwest::Client;
+    use serde_json::json;
+
+    use crate::auth::error_kind::DISCOVERY_FAILURE;
+    use crate::auth::openid_discovery::{try_get_openid_configuration, OPENID_CONFIGURATION_PATH};
+
+    #[tokio::test]
+    pub async fn try_get_openid_configuration_returns_metadata() {
+        let issuer_url = start_server(None).await;
+
+        let configuration = try_get_openid_configuration(&Client::new(), issuer_url.as_str())
+            .await
+            .expect("expected OpenID configuration");
+
+        assert_eq!(format!("{}/", issuer_url), configuration.issuer);
+        assert_eq!(format!("{}/jwks.json", issuer_url), configuration.jwks_uri);
+        assert_eq!(
+            vec![Algorithm::RS256, Algorithm::ES256],
+            configuration.algorithms()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn try_get_openid_configuration_with_mismatching_issuer_returns_error() {
+        let issuer_url = start_server(Some("https://another.example/")).await;
+
+        let error = try_get_openid_configuration(&Client::new(), issuer_url.as_str())
+            .await
+            .expect_err("expected issuer mismatch");
+
+        assert_eq!(DISCOVERY_FAILURE, error.error_kind());
+    }
+
+    #[tokio::test]
+    pub async fn try_get_openid_configuration_unreachable_issuer_returns_error() {
+        let error = try_get_openid_configuration(&Client::new(), "http://127.0.0.1:1")
+            .await
+            .expect_err("expected request failure");
+
+        assert_eq!(DISCOVERY_FAILURE, error.error_kind());
+    }
+
+    async fn start_server(issuer: Option<&str>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("expected listener");
+        let issuer_url = format!(
+            "http://{}",
+            listener.local_addr().expect("expected local address")
+        );
+        let configuration = json!({
+            "issuer": issuer.map(String::from).unwrap_or(format!("{}/", issuer_url)),
+            "jwks_uri": format!("{}/jwks.json", issuer_url),
+            "id_token_signing_alg_values_supported": ["RS256", "ES256", "none"]
+        });
+        let router = Router::new().route(
+            OPENID_CONFIGURATION_PATH,
+            get(move || async move { Json(configuration) }),
+        );
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        issuer_url
+    }
+}
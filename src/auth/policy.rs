@@ -0,0 +1,254 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::fmt::{Debug, Formatter};
+use std::sync::Arc;
+
+use serde_json::Value;
+
+use crate::auth::error_kind::FORBIDDEN;
+use crate::auth::token::Token;
+use crate::error::Error;
+
+type ClaimPredicate = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+/// `Policy` describes the requirements a validated token must fulfill in order to access
+/// a resource. Policies can be combined with `and`/`or` or through `Policy::all`/`Policy::any`.
+#[derive(Clone)]
+pub enum Policy {
+    /// Requires the token's scopes to contain every one of the specified scopes.
+    Scopes(Vec<String>),
+    /// Requires the array (or string) claim `claim` to contain `value`, i.e. `roles` or `permissions`.
+    Contains { claim: String, value: String },
+    /// Requires the claim `name` to exist and fulfill the `predicate`.
+    Claim {
+        name: String,
+        predicate: ClaimPredicate,
+    },
+    /// Requires every inner policy to be fulfilled.
+    All(Vec<Policy>),
+    /// Requires at least one inner policy to be fulfilled.
+    Any(Vec<Policy>),
+}
+
+impl Policy {
+    pub fn scope(scope: impl Into<String>) -> Self {
+        Self::Scopes(vec![scope.into()])
+    }
+
+    pub fn scopes<T: Into<String>>(scopes: impl IntoIterator<Item = T>) -> Self {
+        Self::Scopes(scopes.into_iter().map(Into::into).collect())
+    }
+
+    pub fn role(claim: impl Into<String>, role: impl Into<String>) -> Self {
+        Self::Contains {
+            claim: claim.into(),
+            value: role.into(),
+        }
+    }
+
+    pub fn claim(
+        name: impl Into<String>,
+        predicate: impl Fn(&Value) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        Self::Claim {
+            name: name.into(),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    pub fn claim_equals(name: impl Into<String>, expected_value: Value) -> Self {
+        Self::claim(name, move |value| *value == expected_value)
+    }
+
+    pub fn all(policies: Vec<Policy>) -> Self {
+        Self::All(policies)
+    }
+
+    pub fn any(policies: Vec<Policy>) -> Self {
+        Self::Any(policies)
+    }
+
+    pub fn and(self, other: Policy) -> Self {
+        match self {
+            Self::All(mut policies) => {
+                policies.push(other);
+                Self::All(policies)
+            }
+            policy => Self::All(vec![policy, other]),
+        }
+    }
+
+    pub fn or(self, other: Policy) -> Self {
+        match self {
+            Self::Any(mut policies) => {
+                policies.push(other);
+                Self::Any(policies)
+            }
+            policy => Self::Any(vec![policy, other]),
+        }
+    }
+
+    ///
+    /// Evaluates the policy against the specified token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Previously validated token.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - If the token fulfills the policy.
+    /// * `Err` - Error with the kind `FORBIDDEN` describing the unfulfilled requirement.
+    pub fn evaluate(&self, token: &(dyn Token + Send + Sync)) -> Result<(), Error> {
+        match self {
+            Self::Scopes(scopes) => {
+                let token_scopes = token.scopes();
+
+                match scopes.iter().find(|scope| !token_scopes.contains(scope)) {
+                    Some(scope) => Err(Error::new(
+                        FORBIDDEN,
+                        format!("token is missing the scope '{}'", scope),
+                    )),
+                    None => Ok(()),
+                }
+            }
+            Self::Contains { claim, value } => {
+                let contains = match token.claim_value(claim) {
+                    Some(Value::Array(values)) => values
+                        .iter()
+                        .any(|current| current.as_str() == Some(value.as_str())),
+                    Some(Value::String(current)) => current == *value,
+                    _ => false,
+                };
+
+                if contains {
+                    Ok(())
+                } else {
+                    Err(Error::new(
+                        FORBIDDEN,
+                        format!("claim '{}' does not contain '{}'", claim, value),
+                    ))
+                }
+            }
+            Self::Claim { name, predicate } => match token.claim_value(name) {
+                Some(value) if predicate(&value) => Ok(()),
+                Some(_) => Err(Error::new(
+                    FORBIDDEN,
+                    format!("claim '{}' does not fulfill the policy", name),
+                )),
+                None => Err(Error::new(
+                    FORBIDDEN,
+                    format!("token is missing the claim '{}'", name),
+                )),
+            },
+            Self::All(policies) => {
+                for policy in policies {
+                    policy.evaluate(token)?;
+                }
+
+                Ok(())
+            }
+            Self::Any(policies) => {
+                let mut messages: Vec<String> = Vec::new();
+
+                for policy in policies {
+                    match policy.evaluate(token) {
+                        Ok(_) => return Ok(()),
+                        Err(error) => messages.push(error.message().to_string()),
+                    }
+                }
+
+                Err(Error::new(
+                    FORBIDDEN,
+                    format!(
+                        "token does not fulfill any policy: [{}]",
+                        messages.join("; ")
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+impl Debug for Policy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Scopes(scopes) => f.debug_tuple("Scopes").field(scopes).finish(),
+            Self::Contains { claim, value } => f
+                .debug_struct("Contains")
+                .field("claim", claim)
+                .field("value", value)
+                .finish(),
+            Self::Claim { name, .. } => f.debug_struct("Claim").field("name", name).finish(),
+            Self::All(policies) => f.debug_tuple("All").field(policies).finish(),
+            Self::Any(policies) => f.debug_tuple("Any").field(policies).finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use serde_json::json;
+
+    use crate::auth::error_kind::FORBIDDEN;
+    use crate::auth::policy::Policy;
+    use crate::test_base::get_jwt_token;
+
+    #[test]
+    pub fn scopes_policy_requires_every_scope() {
+        let token = get_jwt_token(json!({ "scope": "read write" }));
+
+        assert!(Policy::scopes(["read", "write"]).evaluate(&token).is_ok());
+        let error = Policy::scopes(["read", "admin"])
+            .evaluate(&token)
+            .expect_err("expected missing scope");
+        assert_eq!(FORBIDDEN, error.error_kind());
+        assert!(error.message().contains("admin"));
+    }
+
+    #[test]
+    pub fn role_policy_checks_array_claim() {
+        let token = get_jwt_token(json!({ "roles": ["admin", "reader"] }));
+
+        assert!(Policy::role("roles", "admin").evaluate(&token).is_ok());
+        assert!(Policy::role("roles", "writer").evaluate(&token).is_err());
+        assert!(Policy::role("permissions", "admin")
+            .evaluate(&token)
+            .is_err());
+    }
+
+    #[test]
+    pub fn claim_policy_applies_predicate() {
+        let token = get_jwt_token(json!({ "tenant": "simpleg", "level": 3 }));
+
+        assert!(Policy::claim_equals("tenant", json!("simpleg"))
+            .evaluate(&token)
+            .is_ok());
+        assert!(
+            Policy::claim("level", |value| value.as_u64().unwrap_or(0) > 5)
+                .evaluate(&token)
+                .is_err()
+        );
+    }
+
+    #[test]
+    pub fn combined_policies_follow_and_or_semantics() {
+        let token = get_jwt_token(json!({ "scope": "read", "roles": ["reader"] }));
+
+        let and_policy = Policy::scope("read").and(Policy::role("roles", "admin"));
+        let or_policy = Policy::role("roles", "admin").or(Policy::scope("read"));
+
+        assert_eq!(
+            FORBIDDEN,
+            and_policy
+                .evaluate(&token)
+                .expect_err("expected 'and' policy failure")
+                .error_kind()
+        );
+        assert!(or_policy.evaluate(&token).is_ok());
+        assert!(Policy::any(vec![]).evaluate(&token).is_err());
+        assert!(Policy::all(vec![]).evaluate(&token).is_ok());
+    }
+}
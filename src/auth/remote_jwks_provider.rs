@@ -0,0 +1,474 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use jsonwebtoken::jwk::{Jwk, JwkSet};
+use reqwest::header::CACHE_CONTROL;
+use reqwest::Client;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::auth::error_kind::JWKS_RETRIEVAL_FAILURE;
+use crate::auth::jwks_provider::JwksProvider;
+use crate::error::Error;
+use crate::ok_or_return_error;
+
+/// Timing settings of a `RemoteJwksProvider`.
+#[derive(Debug, Clone)]
+pub struct RemoteJwksOptions {
+    /// How long the `JwkSet` is cached when the response has no `Cache-Control` max-age.
+    pub default_cache_duration: Duration,
+    /// Lower bound applied to the `Cache-Control` max-age.
+    pub minimum_cache_duration: Duration,
+    /// Upper bound applied to the `Cache-Control` max-age.
+    pub maximum_cache_duration: Duration,
+    /// Minimum time between refreshes triggered by tokens with an unknown `kid`.
+    pub unknown_kid_refresh_interval: Duration,
+    /// Time to wait before retrying a failed refresh.
+    pub retry_interval: Duration,
+}
+
+impl Default for RemoteJwksOptions {
+    fn default() -> Self {
+        Self {
+            default_cache_duration: Duration::from_secs(300),
+            minimum_cache_duration: Duration::from_secs(30),
+            maximum_cache_duration: Duration::from_secs(86400),
+            unknown_kid_refresh_interval: Duration::from_secs(30),
+            retry_interval: Duration::from_secs(10),
+        }
+    }
+}
+
+/// `RemoteJwksProvider` caches the `JwkSet` published at a JWKS URI and keeps it up to date
+/// in the background, so keys rotated by the identity provider are picked up without restarting.
+///
+/// The set is refreshed once the `Cache-Control` max-age of the last response has elapsed, and
+/// whenever a token references an unknown `kid`. The latter is rate limited by
+/// `RemoteJwksOptions::unknown_kid_refresh_interval` so tokens carrying random `kid`s cannot
+/// be used for flooding the identity provider. `find_or_refresh` waits for that refresh, while
+/// `find` only requests it in the background.
+pub struct RemoteJwksProvider {
+    state: Arc<RemoteJwksState>,
+    refresh_task: JoinHandle<()>,
+}
+
+struct RemoteJwksState {
+    jwks_uri: String,
+    client: Client,
+    options: RemoteJwksOptions,
+    jwk_set: RwLock<JwkSet>,
+    next_refresh: Mutex<Instant>,
+    // held while refreshing for an unknown 'kid', so concurrent lookups wait for that refresh
+    last_unknown_kid_refresh: tokio::sync::Mutex<Option<Instant>>,
+    refresh_requested: Notify,
+}
+
+impl RemoteJwksProvider {
+    ///
+    /// Retrieves the `JwkSet` from `jwks_uri` and starts refreshing it in the background.
+    ///
+    /// # Arguments
+    ///
+    /// * `jwks_uri` - URI to be reached for retrieving the `JwkSet`.
+    /// * `client` - HTTP client used for the requests.
+    /// * `options` - Timing settings of the cache.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Provider with the retrieved `JwkSet`.
+    /// * `Err` - Error with the kind `JWKS_RETRIEVAL_FAILURE` if the initial retrieval failed.
+    pub async fn try_new(
+        jwks_uri: String,
+        client: Client,
+        options: RemoteJwksOptions,
+    ) -> Result<Self, Error> {
+        let (jwk_set, max_age) = fetch_jwks(&client, jwks_uri.as_str()).await?;
+        let next_refresh = Instant::now() + cache_duration(&options, max_age);
+
+        let state = Arc::new(RemoteJwksState {
+            jwks_uri,
+            client,
+            options,
+            jwk_set: RwLock::new(jwk_set),
+            next_refresh: Mutex::new(next_refresh),
+            last_unknown_kid_refresh: tokio::sync::Mutex::new(None),
+            refresh_requested: Notify::new(),
+        });
+
+        let refresh_task = tokio::spawn(refresh_loop(state.clone()));
+
+        Ok(Self {
+            state,
+            refresh_task,
+        })
+    }
+
+    /// Retrieves the `JwkSet` right away, replacing the cached one on success.
+    pub async fn refresh(&self) -> Result<(), Error> {
+        self.state.refresh().await
+    }
+}
+
+#[async_trait]
+impl JwksProvider for RemoteJwksProvider {
+    fn find(&self, kid: &str) -> Option<Jwk> {
+        let jwk = self.state.cached_jwk(kid);
+
+        if jwk.is_none() {
+            self.state.request_unknown_kid_refresh();
+        }
+
+        jwk
+    }
+
+    async fn find_or_refresh(&self, kid: &str) -> Option<Jwk> {
+        match self.state.cached_jwk(kid) {
+            Some(jwk) => Some(jwk),
+            None => self.state.refresh_unknown_kid(kid).await,
+        }
+    }
+}
+
+impl Drop for RemoteJwksProvider {
+    fn drop(&mut self) {
+        self.refresh_task.abort();
+    }
+}
+
+impl RemoteJwksState {
+    async fn refresh(&self) -> Result<(), Error> {
+        let (jwk_set, max_age) = fetch_jwks(&self.client, self.jwks_uri.as_str()).await?;
+
+        *self.jwk_set.write().unwrap_or_else(PoisonError::into_inner) = jwk_set;
+        self.schedule_refresh(cache_duration(&self.options, max_age));
+
+        Ok(())
+    }
+
+    fn cached_jwk(&self, kid: &str) -> Option<Jwk> {
+        self.jwk_set
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .find(kid)
+            .cloned()
+    }
+
+    async fn refresh_unknown_kid(&self, kid: &str) -> Option<Jwk> {
+        let mut last_unknown_kid_refresh = self.last_unknown_kid_refresh.lock().await;
+
+        // a concurrent lookup may have refreshed the keys while waiting for the lock
+        if let Some(jwk) = self.cached_jwk(kid) {
+            return Some(jwk);
+        }
+
+        if !self.is_unknown_kid_refresh_allowed(*last_unknown_kid_refresh) {
+            return None;
+        }
+
+        *last_unknown_kid_refresh = Some(Instant::now());
+
+        if let Err(error) = self.refresh().await {
+            log::warn!(
+                "failed to refresh 'JwkSet' from '{}' for 'kid' '{}': {}",
+                self.jwks_uri,
+                kid,
+                error
+            );
+        }
+
+        self.cached_jwk(kid)
+    }
+
+    fn request_unknown_kid_refresh(&self) {
+        // the lock is only held by a lookup which is already refreshing
+        let mut last_unknown_kid_refresh = match self.last_unknown_kid_refresh.try_lock() {
+            Ok(last_unknown_kid_refresh) => last_unknown_kid_refresh,
+            Err(_) => return,
+        };
+
+        if self.is_unknown_kid_refresh_allowed(*last_unknown_kid_refresh) {
+            *last_unknown_kid_refresh = Some(Instant::now());
+            self.refresh_requested.notify_one();
+        }
+    }
+
+    fn is_unknown_kid_refresh_allowed(&self, last_unknown_kid_refresh: Option<Instant>) -> bool {
+        match last_unknown_kid_refresh {
+            Some(last) => last.elapsed() >= self.options.unknown_kid_refresh_interval,
+            None => true,
+        }
+    }
+
+    fn schedule_refresh(&self, after: Duration) {
+        *self
+            .next_refresh
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Instant::now() + after;
+    }
+
+    fn next_refresh(&self) -> Instant {
+        *self
+            .next_refresh
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+async fn refresh_loop(state: Arc<RemoteJwksState>) {
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep_until(state.next_refresh()) => (),
+            _ = state.refresh_requested.notified() => (),
+        }
+
+        if let Err(error) = state.refresh().await {
+            log::warn!(
+                "failed to refresh 'JwkSet' from '{}': {}",
+                state.jwks_uri,
+                error
+            );
+            state.schedule_refresh(state.options.retry_interval);
+        }
+    }
+}
+
+async fn fetch_jwks(client: &Client, jwks_uri: &str) -> Result<(JwkSet, Option<Duration>), Error> {
+    let response = ok_or_return_error!(
+        client.get(jwks_uri).send().await,
+        JWKS_RETRIEVAL_FAILURE,
+        "failed to request the 'JwkSet': "
+    );
+
+    let response = ok_or_return_error!(
+        response.error_for_status(),
+        JWKS_RETRIEVAL_FAILURE,
+        "invalid response obtained getting the 'JwkSet': "
+    );
+
+    let max_age = response
+        .headers()
+        .get(CACHE_CONTROL)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_max_age);
+
+    let jwk_set = ok_or_return_error!(
+        response.json::<JwkSet>().await,
+        JWKS_RETRIEVAL_FAILURE,
+        "response cannot be deserialized as a 'JwkSet': "
+    );
+
+    Ok((jwk_set, max_age))
+}
+
+fn cache_duration(options: &RemoteJwksOptions, max_age: Option<Duration>) -> Duration {
+    match max_age {
+        Some(max_age) => max_age.clamp(
+            options.minimum_cache_duration,
+            options.maximum_cache_duration,
+        ),
+        None => options.default_cache_duration,
+    }
+}
+
+fn parse_max_age(cache_control: &str) -> Option<Duration> {
+    let directives: Vec<&str> = cache_control.split(',').map(str::trim).collect();
+
+    if directives.iter().any(|directive| {
+        directive.eq_ignore_ascii_case("no-store") || directive.eq_ignore_ascii_case("no-cache")
+    }) {
+        return Some(Duration::ZERO);
+    }
+
+    directives.iter().find_map(|directive| {
+        let (name, value) = directive.split_once('=')?;
+
+        if name.trim().eq_ignore_ascii_case("max-age") {
+            value
+                .trim()
+                .trim_matches('"')
+                .parse::<u64>()
+                .ok()
+                .map(Duration::from_secs)
+        } else {
+            None
+        }
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use axum::extract::State;
+    use axum::http::header::CACHE_CONTROL;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use serde_json::{json, Value};
+
+    use crate::auth::jwks_provider::JwksProvider;
+    use crate::auth::remote_jwks_provider::{parse_max_age, RemoteJwksOptions, RemoteJwksProvider};
+
+    #[derive(Clone)]
+    struct JwksServer {
+        kids: Arc<Mutex<Vec<String>>>,
+        hits: Arc<AtomicUsize>,
+    }
+
+    #[tokio::test]
+    pub async fn find_known_kid_returns_key() {
+        let server = JwksServer::new(vec!["first"]);
+        let jwks_uri = server.start().await;
+
+        let provider = RemoteJwksProvider::try_new(jwks_uri, reqwest::Client::new(), get_options())
+            .await
+            .expect("expected provider");
+
+        assert!(provider.find("first").is_some());
+        assert_eq!(1, server.hits());
+    }
+
+    #[tokio::test]
+    pub async fn find_unknown_kid_refreshes_in_background() {
+        let server = JwksServer::new(vec!["first"]);
+        let jwks_uri = server.start().await;
+        let provider = RemoteJwksProvider::try_new(jwks_uri, reqwest::Client::new(), get_options())
+            .await
+            .expect("expected provider");
+        server.set_kids(vec!["first", "rotated"]);
+
+        assert!(provider.find("rotated").is_none());
+        let mut found = false;
+        for _ in 0..50 {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            if provider.find("rotated").is_some() {
+                found = true;
+                break;
+            }
+        }
+
+        assert!(found);
+    }
+
+    #[tokio::test]
+    pub async fn find_or_refresh_unknown_kid_waits_for_refresh() {
+        let server = JwksServer::new(vec!["first"]);
+        let jwks_uri = server.start().await;
+        let provider = RemoteJwksProvider::try_new(jwks_uri, reqwest::Client::new(), get_options())
+            .await
+            .expect("expected provider");
+        server.set_kids(vec!["first", "rotated"]);
+
+        assert!(provider.find_or_refresh("rotated").await.is_some());
+        assert!(provider.find_or_refresh("random").await.is_none());
+        assert_eq!(2, server.hits());
+    }
+
+    #[tokio::test]
+    pub async fn find_unknown_kids_is_rate_limited() {
+        let server = JwksServer::new(vec!["first"]);
+        let jwks_uri = server.start().await;
+        let provider = RemoteJwksProvider::try_new(jwks_uri, reqwest::Client::new(), get_options())
+            .await
+            .expect("expected provider");
+
+        for i in 0..20 {
+            assert!(provider.find(format!("random-{}", i).as_str()).is_none());
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        assert_eq!(2, server.hits());
+    }
+
+    #[tokio::test]
+    pub async fn try_new_unreachable_uri_returns_error() {
+        let result = RemoteJwksProvider::try_new(
+            "http://127.0.0.1:1/jwks.json".to_string(),
+            reqwest::Client::new(),
+            get_options(),
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    pub fn parse_max_age_reads_directive() {
+        assert_eq!(
+            Some(Duration::from_secs(60)),
+            parse_max_age("public, max-age=60, must-revalidate")
+        );
+        assert_eq!(Some(Duration::ZERO), parse_max_age("no-store"));
+        assert_eq!(None, parse_max_age("public"));
+    }
+
+    fn get_options() -> RemoteJwksOptions {
+        RemoteJwksOptions {
+            unknown_kid_refresh_interval: Duration::from_secs(60),
+            ..RemoteJwksOptions::default()
+        }
+    }
+
+    impl JwksServer {
+        fn new(kids: Vec<&str>) -> Self {
+            Self {
+                kids: Arc::new(Mutex::new(kids.into_iter().map(String::from).collect())),
+                hits: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+
+        async fn start(&self) -> String {
+            let router = Router::new()
+                .route("/jwks.json", get(jwks))
+                .with_state(self.clone());
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+                .await
+                .expect("expected listener");
+            let address = listener.local_addr().expect("expected local address");
+            tokio::spawn(async move { axum::serve(listener, router).await });
+
+            format!("http://{}/jwks.json", address)
+        }
+
+        fn set_kids(&self, kids: Vec<&str>) {
+            *self.kids.lock().unwrap() = kids.into_iter().map(String::from).collect();
+        }
+
+        fn hits(&self) -> usize {
+            self.hits.load(Ordering::SeqCst)
+        }
+    }
+
+    async fn jwks(
+        State(server): State<JwksServer>,
+    ) -> ([(&'static str, &'static str); 1], Json<Value>) {
+        server.hits.fetch_add(1, Ordering::SeqCst);
+        let keys: Vec<Value> = server
+            .kids
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|kid| {
+                json!({
+                    "kty": "RSA",
+                    "kid": kid,
+                    "alg": "RS256",
+                    "n": "sXchDaQebHnPiGvyDOAT4saGEUetSyo9MKLOoWFsueri23bOdgWp4Dy1WlUzewbgBHod5pcM9H95GQRV3JDXboIRROSBigeC5yjU1hGzHHyXss8UDprecbAYxknTcQkhslANGRUZmdTOQ5qTRsLAt6BTYuyvVRdhS8exSZEy_c4gs_7svlJJQ4H9_NxsiIoLwAEk7-Q3UXERGYw_75IDrGA84-lA_-Ct4eTlXHBIY2EaV7t7LjJaynVJCpkv4LKjTTAumiGUIuQhrNhZLuF_RJLqHpM2kgWFLU7-VTdL1VbC2tejvcI2BlMkEpk1BzBZI0KQB0GaDWFLN-aEAw3vRw",
+                    "e": "AQAB"
+                })
+            })
+            .collect();
+
+        (
+            [(CACHE_CONTROL.as_str(), "public, max-age=3600")],
+            Json(json!({ "keys": keys })),
+        )
+    }
+}
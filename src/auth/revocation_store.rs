@@ -0,0 +1,303 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use serde::Deserialize;
+use tokio::task::JoinHandle;
+
+use crate::auth::token::Token;
+use crate::config_reader::ConfigReader;
+use crate::error::Error;
+
+/// `RevocationStore` knows which tokens have been revoked before their expiration.
+#[async_trait]
+pub trait RevocationStore {
+    ///
+    /// Checks whether the specified token has been revoked.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Previously validated token.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - `true` if the token has been revoked, either by its `jti` or because every
+    ///   token of its `sub` issued before a point in time has been revoked.
+    /// * `Err` - Error if the revocations could not be checked.
+    async fn is_revoked(&self, token: &(dyn Token + Send + Sync)) -> Result<bool, Error>;
+}
+
+/// `InMemoryRevocationStore` keeps the revocations in memory.
+#[derive(Default)]
+pub struct InMemoryRevocationStore {
+    revoked_token_ids: RwLock<HashSet<String>>,
+    revoked_subjects: RwLock<HashMap<String, u64>>,
+}
+
+impl InMemoryRevocationStore {
+    /// Revokes the token whose `jti` is `token_id`.
+    pub fn revoke_token_id(&self, token_id: impl Into<String>) {
+        self.revoked_token_ids
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(token_id.into());
+    }
+
+    /// Revokes every token of `subject` issued before `issued_before`, in seconds since the
+    /// Unix epoch.
+    pub fn revoke_subject(&self, subject: impl Into<String>, issued_before: u64) {
+        self.revoked_subjects
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(subject.into(), issued_before);
+    }
+
+    /// Replaces every revocation.
+    pub fn replace(
+        &self,
+        revoked_token_ids: HashSet<String>,
+        revoked_subjects: HashMap<String, u64>,
+    ) {
+        *self
+            .revoked_token_ids
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = revoked_token_ids;
+        *self
+            .revoked_subjects
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = revoked_subjects;
+    }
+
+    fn contains(&self, token: &(dyn Token + Send + Sync)) -> bool {
+        if let Some(token_id) = token.token_id() {
+            if self
+                .revoked_token_ids
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains(&token_id)
+            {
+                return true;
+            }
+        }
+
+        let subject = match token.subject() {
+            Some(subject) => subject,
+            None => return false,
+        };
+
+        match self
+            .revoked_subjects
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&subject)
+        {
+            // tokens without 'iat' cannot prove they were issued after the revocation
+            Some(issued_before) => match token.issued_at() {
+                Some(issued_at) => issued_at < *issued_before,
+                None => true,
+            },
+            None => false,
+        }
+    }
+}
+
+#[async_trait]
+impl RevocationStore for InMemoryRevocationStore {
+    async fn is_revoked(&self, token: &(dyn Token + Send + Sync)) -> Result<bool, Error> {
+        Ok(self.contains(token))
+    }
+}
+
+#[derive(Deserialize)]
+struct RevocationList {
+    #[serde(rename = "RevokedTokenIds", default)]
+    revoked_token_ids: HashSet<String>,
+    #[serde(rename = "RevokedSubjects", default)]
+    revoked_subjects: HashMap<String, u64>,
+}
+
+/// `FileRevocationStore` loads the revocations from a YAML file, which can be reloaded
+/// through `refresh`:
+///
+/// ```yaml
+/// RevokedTokenIds:
+///   - "d5c2a1c0-0b9a-4a8e-9d3f-2a1b0c9d8e7f"
+/// RevokedSubjects:
+///   compromised-user: 1700000000
+/// ```
+pub struct FileRevocationStore {
+    file_path: PathBuf,
+    config_reader: ConfigReader,
+    store: InMemoryRevocationStore,
+}
+
+impl FileRevocationStore {
+    ///
+    /// Loads the revocations from the specified file.
+    ///
+    /// # Arguments
+    ///
+    /// * `file_path` - Path of the YAML file containing the revocations.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Store with the loaded revocations.
+    /// * `Err` - Error with the kind `NOT_FOUND` if the file does not exist, or
+    ///   `SERIALIZATION_FAILURE` if it is not valid.
+    pub fn try_new(file_path: PathBuf) -> Result<Self, Error> {
+        let file_revocation_store = Self {
+            file_path,
+            config_reader: ConfigReader::default(),
+            store: InMemoryRevocationStore::default(),
+        };
+
+        file_revocation_store.refresh()?;
+
+        Ok(file_revocation_store)
+    }
+
+    /// Reloads the revocations from the file. Keeps the previous ones if the file is not valid.
+    pub fn refresh(&self) -> Result<(), Error> {
+        let value = self.config_reader.read(self.file_path.clone())?;
+        let revocation_list = serde_yaml::from_value::<RevocationList>(value)?;
+
+        self.store.replace(
+            revocation_list.revoked_token_ids,
+            revocation_list.revoked_subjects,
+        );
+
+        Ok(())
+    }
+
+    /// Spawns a task which reloads the revocations every `interval`.
+    pub fn spawn_refresh(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let file_revocation_store = self.clone();
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                if let Err(error) = file_revocation_store.refresh() {
+                    log::warn!(
+                        "failed to refresh revocations from '{}': {}",
+                        file_revocation_store.file_path.display(),
+                        error
+                    );
+                }
+            }
+        })
+    }
+}
+
+#[async_trait]
+impl RevocationStore for FileRevocationStore {
+    async fn is_revoked(&self, token: &(dyn Token + Send + Sync)) -> Result<bool, Error> {
+        self.store.is_revoked(token).await
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use serde_json::json;
+
+    use crate::auth::revocation_store::{
+        FileRevocationStore, InMemoryRevocationStore, RevocationStore,
+    };
+    use crate::error_kind::NOT_FOUND;
+    use crate::test_base::{get_jwt_token, get_unit_test_data_path};
+
+    #[tokio::test]
+    pub async fn is_revoked_token_id_returns_true() {
+        let store = InMemoryRevocationStore::default();
+        store.revoke_token_id("revoked-jti");
+
+        let revoked = store
+            .is_revoked(&get_jwt_token(json!({ "jti": "revoked-jti" })))
+            .await
+            .expect("expected revocation check");
+        let not_revoked = store
+            .is_revoked(&get_jwt_token(json!({ "jti": "another-jti" })))
+            .await
+            .expect("expected revocation check");
+
+        assert!(revoked);
+        assert!(!not_revoked);
+    }
+
+    #[tokio::test]
+    pub async fn is_revoked_subject_only_revokes_tokens_issued_before() {
+        let store = InMemoryRevocationStore::default();
+        store.revoke_subject("user-1", 1000);
+
+        let old_token = get_jwt_token(json!({ "sub": "user-1", "iat": 999 }));
+        let new_token = get_jwt_token(json!({ "sub": "user-1", "iat": 1000 }));
+        let token_without_iat = get_jwt_token(json!({ "sub": "user-1" }));
+
+        assert!(store.is_revoked(&old_token).await.unwrap());
+        assert!(!store.is_revoked(&new_token).await.unwrap());
+        assert!(store.is_revoked(&token_without_iat).await.unwrap());
+    }
+
+    #[tokio::test]
+    pub async fn file_revocation_store_loads_revocations() {
+        let mut file_path = get_unit_test_data_path(file!());
+        file_path.push("revocations.yaml");
+
+        let store = FileRevocationStore::try_new(file_path).expect("expected revocation store");
+
+        assert!(store
+            .is_revoked(&get_jwt_token(json!({ "jti": "revoked-jti" })))
+            .await
+            .unwrap());
+        assert!(store
+            .is_revoked(&get_jwt_token(
+                json!({ "sub": "compromised-user", "iat": 1600000000 })
+            ))
+            .await
+            .unwrap());
+        assert!(!store
+            .is_revoked(&get_jwt_token(
+                json!({ "sub": "user-1", "jti": "valid-jti" })
+            ))
+            .await
+            .unwrap());
+    }
+
+    #[tokio::test]
+    pub async fn file_revocation_store_refresh_picks_up_changes() {
+        let file_path = std::env::temp_dir().join(format!("{}.yaml", uuid::Uuid::new_v4()));
+        std::fs::write(&file_path, "RevokedTokenIds: []").expect("expected file");
+        let store =
+            FileRevocationStore::try_new(file_path.clone()).expect("expected revocation store");
+        let token = get_jwt_token(json!({ "jti": "revoked-later" }));
+        let revoked_before_refresh = store.is_revoked(&token).await.unwrap();
+
+        std::fs::write(&file_path, "RevokedTokenIds: [\"revoked-later\"]").expect("expected file");
+        let refresh_result = store.refresh();
+        let revoked_after_refresh = store.is_revoked(&token).await.unwrap();
+
+        let _ = std::fs::remove_file(file_path);
+        assert!(refresh_result.is_ok());
+        assert!(!revoked_before_refresh);
+        assert!(revoked_after_refresh);
+    }
+
+    #[test]
+    pub fn file_revocation_store_missing_file_returns_not_found() {
+        let result = FileRevocationStore::try_new("missing-revocations.yaml".into());
+
+        assert_eq!(
+            NOT_FOUND,
+            result.err().expect("expected missing file").error_kind()
+        );
+    }
+}
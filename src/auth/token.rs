@@ -4,8 +4,134 @@
 
 use std::fmt::Debug;
 
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
 #[cfg(test)]
 use mockall::automock;
 
+use crate::error::Error;
+use crate::error_kind::NOT_FOUND;
+
+pub const SUBJECT_CLAIM: &str = "sub";
+pub const ISSUER_CLAIM: &str = "iss";
+pub const AUDIENCE_CLAIM: &str = "aud";
+pub const EXPIRATION_CLAIM: &str = "exp";
+pub const ISSUED_AT_CLAIM: &str = "iat";
+pub const TOKEN_ID_CLAIM: &str = "jti";
+pub const SCOPE_CLAIM: &str = "scope";
+pub const SCP_CLAIM: &str = "scp";
+pub const CONFIRMATION_CLAIM: &str = "cnf";
+
 #[cfg_attr(test, automock)]
-pub trait Token: Debug {}
+pub trait Token: Debug {
+    ///
+    /// Gets the raw value of the specified claim.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the claim, i.e. `sub`.
+    ///
+    /// # Returns
+    ///
+    /// * `Some` - Value of the claim.
+    /// * `None` - If the token does not contain the claim.
+    fn claim_value(&self, name: &str) -> Option<Value>;
+
+    /// Subject (`sub`) of the token.
+    fn subject(&self) -> Option<String> {
+        string_claim(self.claim_value(SUBJECT_CLAIM))
+    }
+
+    /// Issuer (`iss`) of the token.
+    fn issuer(&self) -> Option<String> {
+        string_claim(self.claim_value(ISSUER_CLAIM))
+    }
+
+    /// Audience (`aud`) of the token, which may be either a single string or an array.
+    fn audience(&self) -> Vec<String> {
+        string_list_claim(self.claim_value(AUDIENCE_CLAIM))
+    }
+
+    /// Expiration time (`exp`) of the token as seconds since the Unix epoch.
+    fn expires_at(&self) -> Option<u64> {
+        self.claim_value(EXPIRATION_CLAIM)
+            .and_then(|value| value.as_u64())
+    }
+
+    /// Issued at time (`iat`) of the token as seconds since the Unix epoch.
+    fn issued_at(&self) -> Option<u64> {
+        self.claim_value(ISSUED_AT_CLAIM)
+            .and_then(|value| value.as_u64())
+    }
+
+    /// Unique identifier (`jti`) of the token.
+    fn token_id(&self) -> Option<String> {
+        string_claim(self.claim_value(TOKEN_ID_CLAIM))
+    }
+
+    /// Scopes granted to the token, read from the space-separated `scope` claim or,
+    /// if missing, from the `scp` claim.
+    fn scopes(&self) -> Vec<String> {
+        match self.claim_value(SCOPE_CLAIM) {
+            Some(Value::String(scope)) => scope.split_whitespace().map(String::from).collect(),
+            Some(value) => string_list_claim(Some(value)),
+            None => string_list_claim(self.claim_value(SCP_CLAIM)),
+        }
+    }
+
+    /// Name of the validator which accepted the token, if it was validated by
+    /// `CompositeTokenValidator`.
+    fn validated_by(&self) -> Option<String> {
+        None
+    }
+}
+
+impl dyn Token + Send + Sync {
+    ///
+    /// Gets the specified claim deserialized as `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name of the claim, i.e. `email`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Claim's value.
+    /// * `Err` - Error with the kind `NOT_FOUND` if the claim is missing or
+    ///   `SERIALIZATION_FAILURE` if it could not be deserialized as `T`.
+    pub fn claim<T: DeserializeOwned>(&self, name: &str) -> Result<T, Error> {
+        let value = match self.claim_value(name) {
+            Some(value) => value,
+            None => {
+                return Err(Error::new(
+                    NOT_FOUND,
+                    format!("could not find claim '{}'", name),
+                ))
+            }
+        };
+
+        match serde_json::from_value::<T>(value) {
+            Ok(claim) => Ok(claim),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+fn string_claim(value: Option<Value>) -> Option<String> {
+    match value {
+        Some(Value::String(value)) => Some(value),
+        _ => None,
+    }
+}
+
+fn string_list_claim(value: Option<Value>) -> Vec<String> {
+    match value {
+        Some(Value::String(value)) => vec![value],
+        Some(Value::Array(values)) => values
+            .into_iter()
+            .filter_map(|value| string_claim(Some(value)))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,257 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use jsonwebtoken::get_current_timestamp;
+use jsonwebtoken::jwk::Jwk;
+use sha2::{Digest, Sha256};
+
+use crate::auth::token::Token;
+
+type TokenHash = [u8; 32];
+
+struct CacheEntry {
+    token: Arc<dyn Token + Send + Sync>,
+    key: Option<Jwk>,
+    expires_at: u64,
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<TokenHash, CacheEntry>,
+    // least recently used entries come first
+    recency: BTreeMap<u64, TokenHash>,
+    clock: u64,
+}
+
+impl CacheState {
+    fn touch(&mut self, hash: TokenHash) {
+        self.clock += 1;
+        let clock = self.clock;
+
+        if let Some(entry) = self.entries.get_mut(&hash) {
+            self.recency.remove(&entry.last_used);
+            entry.last_used = clock;
+            self.recency.insert(clock, hash);
+        }
+    }
+
+    fn remove(&mut self, hash: &TokenHash) {
+        if let Some(entry) = self.entries.remove(hash) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+}
+
+/// `TokenCache` is a bounded LRU cache of validated tokens, keyed by the SHA-256 hash of the
+/// raw token. Entries are returned until their expiration and only while the key which validated
+/// them is still the provided one, so only the checks which do not depend on time are skipped.
+pub struct TokenCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+impl TokenCache {
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Maximum number of cached tokens. The least recently used token is evicted
+    ///   when it is reached.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    ///
+    /// Gets the previously validated token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Raw token.
+    /// * `key` - Key currently provided for validating `token`, if any.
+    ///
+    /// # Returns
+    ///
+    /// * `Some` - Validated token, if it is cached, has not expired yet and was validated with
+    ///   `key`.
+    /// * `None` - Otherwise.
+    pub fn get(&self, token: &str, key: Option<&Jwk>) -> Option<Arc<dyn Token + Send + Sync>> {
+        let hash = hash(token);
+        let mut state = self.state.lock().ok()?;
+        let entry = state.entries.get(&hash)?;
+
+        if entry.expires_at <= get_current_timestamp() || entry.key.as_ref() != key {
+            state.remove(&hash);
+            return None;
+        }
+
+        state.touch(hash);
+        state.entries.get(&hash).map(|entry| entry.token.clone())
+    }
+
+    ///
+    /// Caches a validated token.
+    ///
+    /// # Arguments
+    ///
+    /// * `token` - Raw token.
+    /// * `validated_token` - Result of validating `token`.
+    /// * `key` - Key which validated `token`, if any.
+    /// * `expires_at` - Timestamp, in seconds, until which `validated_token` may be returned.
+    pub fn insert(
+        &self,
+        token: &str,
+        validated_token: Arc<dyn Token + Send + Sync>,
+        key: Option<Jwk>,
+        expires_at: u64,
+    ) {
+        if self.capacity == 0 || expires_at <= get_current_timestamp() {
+            return;
+        }
+
+        let hash = hash(token);
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return,
+        };
+
+        state.remove(&hash);
+
+        while state.entries.len() >= self.capacity {
+            let oldest = match state.recency.first_key_value() {
+                Some((_, oldest)) => *oldest,
+                None => break,
+            };
+
+            state.remove(&oldest);
+        }
+
+        state.clock += 1;
+        let clock = state.clock;
+        state.entries.insert(
+            hash,
+            CacheEntry {
+                token: validated_token,
+                key,
+                expires_at,
+                last_used: clock,
+            },
+        );
+        state.recency.insert(clock, hash);
+    }
+
+    pub fn len(&self) -> usize {
+        match self.state.lock() {
+            Ok(state) => state.entries.len(),
+            Err(_) => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn hash(token: &str) -> TokenHash {
+    Sha256::digest(token.as_bytes()).into()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    use jsonwebtoken::get_current_timestamp;
+    use jsonwebtoken::jwk::Jwk;
+    use serde_json::{json, Value};
+
+    use crate::auth::claims_token::ClaimsToken;
+    use crate::auth::token_cache::TokenCache;
+
+    #[test]
+    pub fn get_returns_cached_token_until_expiration() {
+        let token_cache = TokenCache::new(10);
+        let now = get_current_timestamp();
+        token_cache.insert("valid", get_token("user-1"), None, now + 60);
+        token_cache.insert("expired", get_token("user-2"), None, now - 1);
+
+        let token = token_cache
+            .get("valid", None)
+            .expect("expected cached token");
+
+        assert_eq!(Some("user-1".to_string()), token.subject());
+        assert!(token_cache.get("expired", None).is_none());
+        assert!(token_cache.get("unknown", None).is_none());
+        assert_eq!(1, token_cache.len());
+    }
+
+    #[test]
+    pub fn insert_evicts_least_recently_used_token() {
+        let token_cache = TokenCache::new(2);
+        let expires_at = get_current_timestamp() + 60;
+        token_cache.insert("first", get_token("user-1"), None, expires_at);
+        token_cache.insert("second", get_token("user-2"), None, expires_at);
+        token_cache.get("first", None);
+
+        token_cache.insert("third", get_token("user-3"), None, expires_at);
+
+        assert!(token_cache.get("first", None).is_some());
+        assert!(token_cache.get("second", None).is_none());
+        assert!(token_cache.get("third", None).is_some());
+        assert_eq!(2, token_cache.len());
+    }
+
+    #[test]
+    pub fn insert_without_capacity_does_not_cache() {
+        let token_cache = TokenCache::new(0);
+
+        token_cache.insert(
+            "valid",
+            get_token("user-1"),
+            None,
+            get_current_timestamp() + 60,
+        );
+
+        assert!(token_cache.is_empty());
+    }
+
+    #[test]
+    pub fn get_returns_cached_token_only_for_validating_key() {
+        let token_cache = TokenCache::new(10);
+        let expires_at = get_current_timestamp() + 60;
+        token_cache.insert(
+            "valid",
+            get_token("user-1"),
+            Some(get_jwk("x-1")),
+            expires_at,
+        );
+
+        assert!(token_cache.get("valid", Some(&get_jwk("x-1"))).is_some());
+        assert!(token_cache.get("valid", Some(&get_jwk("x-2"))).is_none());
+        assert!(token_cache.get("valid", Some(&get_jwk("x-1"))).is_none());
+        assert!(token_cache.is_empty());
+    }
+
+    fn get_token(subject: &str) -> Arc<ClaimsToken> {
+        let claims: HashMap<String, Value> =
+            serde_json::from_value(json!({ "sub": subject })).expect("expected claims map");
+
+        Arc::new(ClaimsToken::new(claims))
+    }
+
+    fn get_jwk(x: &str) -> Jwk {
+        serde_json::from_value(json!({
+            "kty": "OKP",
+            "crv": "Ed25519",
+            "kid": "key",
+            "x": x
+        }))
+        .expect("expected jwk")
+    }
+}
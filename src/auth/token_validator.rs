@@ -22,6 +22,12 @@ pub trait TokenValidator {
     ///
     /// * `Ok` - Token.
     /// * `Err` - Error if the validation has failed.
-    /// Having the error kind valued as `INVALID_TOKEN` if the token is invalid.
+    ///   Having the error kind valued as `INVALID_TOKEN` if the token is invalid.
     fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error>;
 }
+
+impl<T: TokenValidator + ?Sized> TokenValidator for Arc<T> {
+    fn validate(&self, token: &str) -> Result<Arc<dyn Token + Send + Sync>, Error> {
+        self.as_ref().validate(token)
+    }
+}
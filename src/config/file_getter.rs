@@ -24,7 +24,7 @@ impl FileGetter {
         }
     }
 
-    fn inner_value(value: &Value, keys: &[&str], index: usize) -> Result<Value, Error> {
+    pub(crate) fn inner_value(value: &Value, keys: &[&str], index: usize) -> Result<Value, Error> {
         let current_key = keys[index];
 
         let current_value = match value.get(current_key) {
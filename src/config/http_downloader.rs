@@ -10,38 +10,38 @@ use tokio::time::timeout;
 
 use crate::config::downloader::Downloader;
 use crate::error::Error;
-use crate::error_kind::TIMED_OUT;
+use crate::error_kind::{ErrorKind, TIMED_OUT};
+use crate::retry::{log_retry, RetryPolicy};
 
 pub struct HttpDownloader {
     access_token: String,
     download_timeout: Duration,
     client: Client,
+    retry_policy: RetryPolicy,
 }
 
 impl HttpDownloader {
+    /// Retryable failures are retried as long as the next attempt starts within
+    /// `download_timeout` of the first one, so a download takes at most about twice the timeout.
     pub fn new(access_token: String, download_timeout: Duration, client: Client) -> Self {
         Self {
             access_token,
             download_timeout,
             client,
+            retry_policy: RetryPolicy::default()
+                .with_max_elapsed_time(download_timeout)
+                .with_on_retry(log_retry),
         }
     }
-}
 
-#[async_trait]
-impl Downloader for HttpDownloader {
-    async fn download(
-        &self,
-        host: &str,
-        stage: &str,
-        environment: &str,
-        component: &str,
-    ) -> Result<Vec<u8>, Error> {
-        let url = format!(
-            "{}/config?stage={}&environment={}&component={}",
-            host, stage, environment, component
-        );
+    /// Replaces the policy for retrying the downloads which failed with a retryable error. The
+    /// download timeout applies to every attempt.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
 
+    async fn try_download(&self, url: &str) -> Result<Vec<u8>, Error> {
         let request_builder = self.client.get(url).bearer_auth(self.access_token.clone());
 
         let response = match timeout(self.download_timeout, request_builder.send()).await {
@@ -57,6 +57,18 @@ impl Downloader for HttpDownloader {
             }
         };
 
+        let status_code = response.status();
+
+        if !status_code.is_success() {
+            return Err(Error::new(
+                ErrorKind::from_status_code(status_code.as_u16()),
+                format!(
+                    "configuration download failed with status '{}'",
+                    status_code
+                ),
+            ));
+        }
+
         let package_data = match response.bytes().await {
             Ok(package_data) => package_data,
             Err(error) => return Err(error.into()),
@@ -66,18 +78,43 @@ impl Downloader for HttpDownloader {
     }
 }
 
+#[async_trait]
+impl Downloader for HttpDownloader {
+    async fn download(
+        &self,
+        host: &str,
+        stage: &str,
+        environment: &str,
+        component: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let url = format!(
+            "{}/config?stage={}&environment={}&component={}",
+            host, stage, environment, component
+        );
+
+        self.retry_policy
+            .retry(|| self.try_download(url.as_str()))
+            .await
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
     use std::time::Duration;
 
     use reqwest::Client;
     use serde_yaml::Value;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpListener;
     use tokio::time::Instant;
 
     use crate::config::downloader::Downloader;
     use crate::config::http_downloader::HttpDownloader;
     use crate::config_reader::ConfigReader;
-    use crate::error_kind::TIMED_OUT;
+    use crate::error_kind::{TIMED_OUT, TOO_MANY_REQUESTS};
+    use crate::retry::{Jitter, RetryPolicy};
     use crate::secrets::get_secrets_manager;
     use crate::test_base::get_unit_test_data_path;
 
@@ -110,6 +147,107 @@ pub mod tests {
         assert!(result.unwrap().len() > 0);
     }
 
+    #[tokio::test]
+    pub async fn download_rate_limited_config_returns_retryable_error() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("expected listener");
+        let host = format!(
+            "http://{}",
+            listener.local_addr().expect("expected local address")
+        );
+        tokio::spawn(async move {
+            if let Ok((mut stream, _)) = listener.accept().await {
+                let _ = stream
+                    .write_all(b"HTTP/1.1 429 Too Many Requests\r\ncontent-length: 0\r\n\r\n")
+                    .await;
+            }
+        });
+        let downloader =
+            HttpDownloader::new("token".to_string(), Duration::from_secs(5), Client::new())
+                .with_retry_policy(RetryPolicy::default().with_max_attempts(1));
+
+        let error = downloader
+            .download(host.as_str(), "stage", "environment", "component")
+            .await
+            .expect_err("expected rate limited download");
+
+        assert_eq!(TOO_MANY_REQUESTS, error.error_kind());
+        assert!(error.is_retryable());
+    }
+
+    #[tokio::test]
+    pub async fn download_unavailable_config_retries_until_success() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("expected listener");
+        let host = format!(
+            "http://{}",
+            listener.local_addr().expect("expected local address")
+        );
+        tokio::spawn(async move {
+            let responses: [&[u8]; 2] = [
+                b"HTTP/1.1 503 Service Unavailable\r\nconnection: close\r\ncontent-length: 0\r\n\r\n",
+                b"HTTP/1.1 200 OK\r\nconnection: close\r\ncontent-length: 7\r\n\r\npackage",
+            ];
+
+            for response in responses {
+                if let Ok((mut stream, _)) = listener.accept().await {
+                    let _ = stream.write_all(response).await;
+                }
+            }
+        });
+        let downloader =
+            HttpDownloader::new("token".to_string(), Duration::from_secs(5), Client::new())
+                .with_retry_policy(
+                    RetryPolicy::default()
+                        .with_jitter(Jitter::None)
+                        .with_initial_delay(Duration::ZERO),
+                );
+
+        let package_data = downloader
+            .download(host.as_str(), "stage", "environment", "component")
+            .await
+            .expect("expected package after retrying");
+
+        assert_eq!(b"package".to_vec(), package_data);
+    }
+
+    #[tokio::test]
+    pub async fn download_timed_out_config_is_not_retried_past_download_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("expected listener");
+        let host = format!(
+            "http://{}",
+            listener.local_addr().expect("expected local address")
+        );
+        let connections = Arc::new(AtomicUsize::new(0));
+        let accepted_connections = connections.clone();
+        tokio::spawn(async move {
+            let mut streams = Vec::new();
+
+            // keeps the connections open without ever responding
+            while let Ok((stream, _)) = listener.accept().await {
+                accepted_connections.fetch_add(1, Ordering::SeqCst);
+                streams.push(stream);
+            }
+        });
+        let downloader = HttpDownloader::new(
+            "token".to_string(),
+            Duration::from_millis(100),
+            Client::new(),
+        );
+
+        let error = downloader
+            .download(host.as_str(), "stage", "environment", "component")
+            .await
+            .expect_err("expected timed out download");
+
+        assert_eq!(TIMED_OUT, error.error_kind());
+        assert_eq!(1, connections.load(Ordering::SeqCst));
+    }
+
     fn get_config() -> Value {
         let config_reader = ConfigReader::default();
         let mut config_path = get_unit_test_data_path(file!());
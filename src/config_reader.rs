@@ -6,28 +6,18 @@ use std::path::PathBuf;
 
 use serde_yaml::Value;
 
-use crate::{error::Error, error_kind::NOT_FOUND};
+use crate::error::{Error, ResultExt};
 
 #[derive(Default)]
 pub struct ConfigReader {}
 
 impl ConfigReader {
     pub fn read(&self, config_file_path: PathBuf) -> Result<Value, Error> {
-        let result = std::fs::read_to_string(config_file_path);
+        let yaml = std::fs::read_to_string(&config_file_path)
+            .with_context(|| format!("failed to read '{}'", config_file_path.display()))?;
 
-        match result {
-            Ok(yaml) => match serde_yaml::from_str(yaml.as_str()) {
-                Ok(value) => Ok(value),
-                Err(error) => Err(error.into()),
-            },
-            Err(error) => {
-                if error.kind().to_string().contains("entity not found") {
-                    Err(Error::new(NOT_FOUND, error.to_string()))
-                } else {
-                    Err(error.into())
-                }
-            }
-        }
+        serde_yaml::from_str(yaml.as_str())
+            .with_context(|| format!("failed to parse '{}'", config_file_path.display()))
     }
 }
 
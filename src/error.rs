@@ -2,26 +2,67 @@
  * Copyright (c) Gabriel Amihalachioaie, SimpleG 2023.
  */
 
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 
 use zip::result::ZipError;
 
-use crate::error_kind::{SERIALIZATION_FAILURE, REQUEST_FAILURE, COMPRESSION_FAILURE};
+use log::Level;
 
-#[derive(Debug, Clone, PartialOrd, PartialEq)]
+use crate::error_kind::{ErrorKind, ErrorKindMetadata, Fault};
+use crate::macros::with_cause;
+
+type Source = Arc<dyn std::error::Error + Send + Sync>;
+
+/// `Error` is the error of every operation of this crate. It keeps the error it was created
+/// from, if any, as its `source`, while its message accumulates the context added along the way
+/// through `ResultExt`.
+#[derive(Debug, Clone)]
 pub struct Error {
-    error_kind: String,
+    error_kind: ErrorKind,
     message: String,
+    source: Option<Source>,
 }
 
 impl Error {
-    pub fn new(error_kind: impl Into<String>, message: impl Into<String>) -> Self {
+    ///
+    /// # Arguments
+    ///
+    /// * `error_kind` - Kind of the error, either an `ErrorKind` or one of the error kind
+    ///   constants, i.e. `NOT_FOUND`.
+    /// * `message` - Description of the error.
+    pub fn new(error_kind: impl Into<ErrorKind>, message: impl Into<String>) -> Self {
         Self {
             error_kind: error_kind.into(),
             message: message.into(),
+            source: None,
         }
     }
 
+    /// Keeps `source` as the underlying cause of the error.
+    pub fn with_source(mut self, source: impl std::error::Error + Send + Sync + 'static) -> Self {
+        self.source = Some(Arc::new(source));
+        self
+    }
+
+    ///
+    /// Prepends `context` to the message, keeping the kind and source of the error.
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - What was being done when the error happened, i.e. the path being read.
+    pub fn context(mut self, context: impl Display) -> Self {
+        self.message = format!("{}: {}", context, self.message);
+        self
+    }
+
+    /// Typed kind of the error, for matching it.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.error_kind
+    }
+
+    /// Name of the kind of the error, i.e. `not_found`.
     pub fn error_kind(&self) -> &str {
         self.error_kind.as_str()
     }
@@ -29,6 +70,29 @@ impl Error {
     pub fn message(&self) -> &str {
         self.message.as_str()
     }
+
+    /// Metadata of the kind of the error, see `ErrorKind::metadata`.
+    pub fn metadata(&self) -> ErrorKindMetadata {
+        self.error_kind.metadata()
+    }
+
+    /// If the failed operation may be retried as it is, i.e. after a timeout.
+    pub fn is_retryable(&self) -> bool {
+        self.metadata().is_retryable()
+    }
+
+    /// If the cause of the error is expected to go away by itself.
+    pub fn is_transient(&self) -> bool {
+        self.metadata().is_transient()
+    }
+
+    pub fn fault(&self) -> Fault {
+        self.metadata().fault()
+    }
+
+    pub fn log_level(&self) -> Level {
+        self.metadata().log_level()
+    }
 }
 
 impl Display for Error {
@@ -37,47 +101,224 @@ impl Display for Error {
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.source
+            .as_deref()
+            .map(|source| source as &(dyn std::error::Error + 'static))
+    }
+}
+
+// the source is not comparable, so errors are equal if their kind and message are
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        self.error_kind == other.error_kind && self.message == other.message
+    }
+}
+
+impl PartialOrd for Error {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match self.error_kind.partial_cmp(&other.error_kind) {
+            Some(Ordering::Equal) => self.message.partial_cmp(&other.message),
+            ordering => ordering,
+        }
+    }
+}
+
+/// `ResultExt` adds context to the error of a `Result` while it propagates, converting it into
+/// an `Error` if needed.
+pub trait ResultExt<T> {
+    ///
+    /// # Arguments
+    ///
+    /// * `context` - What was being done when the error happened, i.e. the path being read.
+    fn context(self, context: impl Display) -> Result<T, Error>;
+
+    /// Same as `context`, but the context is only built if there is an error.
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn context(self, context: impl Display) -> Result<T, Error> {
+        self.map_err(|error| error.into().context(context))
+    }
+
+    fn with_context<C: Display>(self, context: impl FnOnce() -> C) -> Result<T, Error> {
+        self.map_err(|error| error.into().context(context()))
+    }
+}
+
+/// `OrError` turns a failed `Result` or an empty `Option` into an `Error` of the specified kind,
+/// the same as `ok_or_return_error!` and `some_or_return_error!` do.
+pub trait OrError<T> {
+    ///
+    /// # Arguments
+    ///
+    /// * `error_kind` - Kind of the error, either an `ErrorKind` or an error kind constant.
+    /// * `message` - Description of the error, followed by the original error, if any.
+    fn or_error(self, error_kind: impl Into<ErrorKind>, message: impl Display) -> Result<T, Error>;
+
+    /// Same as `or_error`, but the message is only built if there is an error.
+    fn or_error_with<M: Display>(
+        self,
+        error_kind: impl Into<ErrorKind>,
+        message: impl FnOnce() -> M,
+    ) -> Result<T, Error>;
+}
+
+impl<T, E: Display> OrError<T> for Result<T, E> {
+    fn or_error(self, error_kind: impl Into<ErrorKind>, message: impl Display) -> Result<T, Error> {
+        self.map_err(|error| {
+            Error::new(error_kind, with_cause(message.to_string().as_str(), &error))
+        })
+    }
+
+    fn or_error_with<M: Display>(
+        self,
+        error_kind: impl Into<ErrorKind>,
+        message: impl FnOnce() -> M,
+    ) -> Result<T, Error> {
+        self.map_err(|error| {
+            Error::new(
+                error_kind,
+                with_cause(message().to_string().as_str(), &error),
+            )
+        })
+    }
+}
+
+impl<T> OrError<T> for Option<T> {
+    fn or_error(self, error_kind: impl Into<ErrorKind>, message: impl Display) -> Result<T, Error> {
+        self.ok_or_else(|| Error::new(error_kind, message.to_string()))
+    }
+
+    fn or_error_with<M: Display>(
+        self,
+        error_kind: impl Into<ErrorKind>,
+        message: impl FnOnce() -> M,
+    ) -> Result<T, Error> {
+        self.ok_or_else(|| Error::new(error_kind, message().to_string()))
+    }
+}
+
 impl From<serde_yaml::Error> for Error {
     fn from(value: serde_yaml::Error) -> Self {
-        Self {
-            error_kind: SERIALIZATION_FAILURE.to_string(),
-            message: value.to_string(),
-        }
+        Self::new(ErrorKind::SerializationFailure, value.to_string()).with_source(value)
     }
 }
 
 impl From<serde_json::Error> for Error {
     fn from(value: serde_json::Error) -> Self {
-        Self {
-            error_kind: SERIALIZATION_FAILURE.to_string(),
-            message: value.to_string(),
-        }
+        Self::new(ErrorKind::SerializationFailure, value.to_string()).with_source(value)
     }
 }
 
 impl From<std::io::Error> for Error {
     fn from(value: std::io::Error) -> Self {
-        Self {
-            error_kind: value.kind().to_string(),
-            message: value.to_string(),
-        }
+        Self::new(ErrorKind::from(value.kind()), value.to_string()).with_source(value)
     }
 }
 
 impl From<reqwest::Error> for Error {
     fn from(value: reqwest::Error) -> Self {
-        Self {
-            error_kind: REQUEST_FAILURE.to_string(),
-            message: value.to_string()
-        }
+        let error_kind = if value.is_timeout() {
+            ErrorKind::TimedOut
+        } else if value.is_connect() {
+            ErrorKind::Unavailable
+        } else {
+            match value.status() {
+                Some(status_code) => ErrorKind::from_status_code(status_code.as_u16()),
+                None => ErrorKind::RequestFailure,
+            }
+        };
+
+        Self::new(error_kind, value.to_string()).with_source(value)
     }
 }
 
 impl From<ZipError> for Error {
     fn from(value: ZipError) -> Self {
-        Self {
-            error_kind: COMPRESSION_FAILURE.to_string(),
-            message: value.to_string()
-        }
+        Self::new(ErrorKind::CompressionFailure, value.to_string()).with_source(value)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::error::Error as _;
+    use std::io;
+
+    use crate::error::{Error, OrError, ResultExt};
+    use crate::error_kind::{ErrorKind, NOT_FOUND};
+
+    #[test]
+    pub fn new_with_constant_is_typed() {
+        let error = Error::new(NOT_FOUND, "missing");
+
+        assert_eq!(&ErrorKind::NotFound, error.kind());
+        assert_eq!(NOT_FOUND, error.error_kind());
+        assert_eq!("not_found: missing", error.to_string());
+    }
+
+    #[test]
+    pub fn from_io_error_maps_not_found() {
+        let error = Error::from(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+
+        assert_eq!(&ErrorKind::NotFound, error.kind());
+        assert_eq!(NOT_FOUND, error.error_kind());
+    }
+
+    #[test]
+    pub fn from_serde_error_keeps_source() {
+        let error = Error::from(
+            serde_json::from_str::<u32>("not a number").expect_err("expected invalid JSON"),
+        );
+
+        let source = error.source().expect("expected source");
+
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+    }
+
+    #[test]
+    pub fn context_accumulates_while_keeping_kind_and_source() {
+        let result: Result<(), io::Error> =
+            Err(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+
+        let error = result
+            .context("failed to read 'config.yaml'")
+            .with_context(|| format!("failed to load '{}'", "Example"))
+            .expect_err("expected error");
+
+        assert_eq!(&ErrorKind::NotFound, error.kind());
+        assert_eq!(
+            "failed to load 'Example': failed to read 'config.yaml': no such file",
+            error.message()
+        );
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    pub fn error_converts_into_boxed_error() {
+        let boxed: Box<dyn std::error::Error + Send + Sync> =
+            Box::new(Error::new(NOT_FOUND, "missing"));
+
+        assert_eq!("not_found: missing", boxed.to_string());
+    }
+
+    #[test]
+    pub fn or_error_converts_result_and_option() {
+        let result = "abc"
+            .parse::<u32>()
+            .or_error(NOT_FOUND, "failed to parse the port");
+        let option = None::<u32>.or_error_with(NOT_FOUND, || format!("'{}' is missing", "Port"));
+
+        assert_eq!(
+            "failed to parse the port: invalid digit found in string",
+            result.expect_err("expected parse failure").message()
+        );
+        assert_eq!(
+            "'Port' is missing",
+            option.expect_err("expected missing value").message()
+        );
+        assert_eq!(Ok(8080), Some(8080).or_error(NOT_FOUND, "unused"));
     }
 }
@@ -2,6 +2,12 @@
  * Copyright (c) Gabriel Amihalachioaie, SimpleG 2023.
  */
 
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{OnceLock, RwLock};
+
+use log::Level;
+
 pub const SERIALIZATION_FAILURE: &str = "serialization_failure";
 pub const SECRETS_MANAGER_FAILURE: &str = "secrets_manager_failure";
 pub const REQUEST_FAILURE: &str = "request_failure";
@@ -9,3 +15,388 @@ pub const TIMED_OUT: &str = "timed_out";
 pub const COMPRESSION_FAILURE: &str = "compression_failure";
 pub const NOT_FOUND: &str = "not_found";
 pub const NOT_IMPLEMENTED: &str = "not_implemented";
+pub const IO_FAILURE: &str = "io_failure";
+pub const TOO_MANY_REQUESTS: &str = "too_many_requests";
+pub const UNAVAILABLE: &str = "unavailable";
+
+static REGISTERED_METADATA: OnceLock<RwLock<HashMap<String, ErrorKindMetadata>>> = OnceLock::new();
+
+/// `ErrorKind` is the typed counterpart of the error kind constants, for matching errors
+/// without comparing strings. Kinds unknown to this crate are kept as `Other`.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ErrorKind {
+    SerializationFailure,
+    SecretsManagerFailure,
+    RequestFailure,
+    TimedOut,
+    CompressionFailure,
+    NotFound,
+    NotImplemented,
+    IoFailure,
+    TooManyRequests,
+    Unavailable,
+    JwksRetrievalFailure,
+    MalformedToken,
+    InvalidHeaders,
+    InvalidToken,
+    Forbidden,
+    InvalidAlgorithm,
+    DiscoveryFailure,
+    IntrospectionFailure,
+    RevokedToken,
+    MissingCredentials,
+    MalformedCredentials,
+    MultipleCredentials,
+    UnsupportedAuthorizationScheme,
+    InvalidDpopProof,
+    InvalidKey,
+    SigningFailure,
+    MissingAuthenticationLayer,
+    Other(String),
+}
+
+impl ErrorKind {
+    /// Name of the kind, i.e. `not_found`, matching the error kind constants.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::SerializationFailure => SERIALIZATION_FAILURE,
+            Self::SecretsManagerFailure => SECRETS_MANAGER_FAILURE,
+            Self::RequestFailure => REQUEST_FAILURE,
+            Self::TimedOut => TIMED_OUT,
+            Self::CompressionFailure => COMPRESSION_FAILURE,
+            Self::NotFound => NOT_FOUND,
+            Self::NotImplemented => NOT_IMPLEMENTED,
+            Self::IoFailure => IO_FAILURE,
+            Self::TooManyRequests => TOO_MANY_REQUESTS,
+            Self::Unavailable => UNAVAILABLE,
+            // the constants of 'auth::error_kind' are only compiled along with the 'auth' feature
+            Self::JwksRetrievalFailure => "jwks_retrieval_failure",
+            Self::MalformedToken => "malformed_token",
+            Self::InvalidHeaders => "invalid_headers",
+            Self::InvalidToken => "invalid_token",
+            Self::Forbidden => "forbidden",
+            Self::InvalidAlgorithm => "invalid_algorithm",
+            Self::DiscoveryFailure => "discovery_failure",
+            Self::IntrospectionFailure => "introspection_failure",
+            Self::RevokedToken => "revoked_token",
+            Self::MissingCredentials => "missing_credentials",
+            Self::MalformedCredentials => "malformed_credentials",
+            Self::MultipleCredentials => "multiple_credentials",
+            Self::UnsupportedAuthorizationScheme => "unsupported_authorization_scheme",
+            Self::InvalidDpopProof => "invalid_dpop_proof",
+            Self::InvalidKey => "invalid_key",
+            Self::SigningFailure => "signing_failure",
+            Self::MissingAuthenticationLayer => "missing_authentication_layer",
+            Self::Other(error_kind) => error_kind.as_str(),
+        }
+    }
+
+    ///
+    /// Maps the HTTP status code of a failed upstream response to an error kind. Rejections such
+    /// as `401`, `403` or `404` are failures of this service rather than of its client, so they
+    /// map to `RequestFailure` instead of the kinds used when validating the client's requests.
+    ///
+    /// # Arguments
+    ///
+    /// * `status_code` - Status code of the response, i.e. `429`.
+    pub fn from_status_code(status_code: u16) -> Self {
+        match status_code {
+            408 | 504 => Self::TimedOut,
+            429 => Self::TooManyRequests,
+            501 => Self::NotImplemented,
+            500..=599 => Self::Unavailable,
+            _ => Self::RequestFailure,
+        }
+    }
+
+    /// Metadata of the kind. The kinds unknown to this crate get the metadata registered
+    /// through `ErrorKindMetadata::register`, if any.
+    pub fn metadata(&self) -> ErrorKindMetadata {
+        let server_error = ErrorKindMetadata::new(false, false, Fault::Server, Level::Error);
+        let client_error = ErrorKindMetadata::new(false, false, Fault::Client, Level::Info);
+        let transient_error = ErrorKindMetadata::new(true, true, Fault::Server, Level::Warn);
+
+        match self {
+            Self::TimedOut
+            | Self::Unavailable
+            | Self::JwksRetrievalFailure
+            | Self::DiscoveryFailure
+            | Self::IntrospectionFailure => transient_error,
+            Self::TooManyRequests => ErrorKindMetadata::new(true, true, Fault::Client, Level::Warn),
+            Self::NotFound
+            | Self::MalformedToken
+            | Self::InvalidHeaders
+            | Self::InvalidToken
+            | Self::Forbidden
+            | Self::InvalidAlgorithm
+            | Self::RevokedToken
+            | Self::MissingCredentials
+            | Self::MalformedCredentials
+            | Self::MultipleCredentials
+            | Self::UnsupportedAuthorizationScheme
+            | Self::InvalidDpopProof => client_error,
+            Self::Other(error_kind) => {
+                let registered_metadata = REGISTERED_METADATA
+                    .get()
+                    .and_then(|registered_metadata| registered_metadata.read().ok())
+                    .and_then(|registered_metadata| registered_metadata.get(error_kind).copied());
+
+                registered_metadata.unwrap_or(server_error)
+            }
+            _ => server_error,
+        }
+    }
+}
+
+/// `Fault` tells which side caused an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fault {
+    /// The request was wrong, i.e. missing credentials or unknown resource.
+    Client,
+    /// The request was right, but it could not be fulfilled.
+    Server,
+}
+
+/// `ErrorKindMetadata` describes how the errors of a kind should be handled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ErrorKindMetadata {
+    retryable: bool,
+    transient: bool,
+    fault: Fault,
+    log_level: Level,
+}
+
+impl ErrorKindMetadata {
+    ///
+    /// # Arguments
+    ///
+    /// * `retryable` - If the failed operation may be retried as it is.
+    /// * `transient` - If the cause of the error is expected to go away by itself.
+    /// * `fault` - Side which caused the error.
+    /// * `log_level` - Level at which the errors should be logged.
+    pub fn new(retryable: bool, transient: bool, fault: Fault, log_level: Level) -> Self {
+        Self {
+            retryable,
+            transient,
+            fault,
+            log_level,
+        }
+    }
+
+    ///
+    /// Registers the metadata of a kind unknown to this crate, i.e. a kind specific to a service.
+    ///
+    /// # Arguments
+    ///
+    /// * `error_kind` - Name of the kind, i.e. `quota_exceeded`.
+    /// * `metadata` - Metadata of the kind.
+    pub fn register(error_kind: impl Into<String>, metadata: ErrorKindMetadata) {
+        let registered_metadata = REGISTERED_METADATA.get_or_init(|| RwLock::new(HashMap::new()));
+
+        if let Ok(mut registered_metadata) = registered_metadata.write() {
+            registered_metadata.insert(error_kind.into(), metadata);
+        }
+    }
+
+    pub fn is_retryable(&self) -> bool {
+        self.retryable
+    }
+
+    pub fn is_transient(&self) -> bool {
+        self.transient
+    }
+
+    pub fn fault(&self) -> Fault {
+        self.fault
+    }
+
+    pub fn log_level(&self) -> Level {
+        self.log_level
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl From<&str> for ErrorKind {
+    fn from(value: &str) -> Self {
+        match value {
+            SERIALIZATION_FAILURE => Self::SerializationFailure,
+            SECRETS_MANAGER_FAILURE => Self::SecretsManagerFailure,
+            REQUEST_FAILURE => Self::RequestFailure,
+            TIMED_OUT => Self::TimedOut,
+            COMPRESSION_FAILURE => Self::CompressionFailure,
+            NOT_FOUND => Self::NotFound,
+            NOT_IMPLEMENTED => Self::NotImplemented,
+            IO_FAILURE => Self::IoFailure,
+            TOO_MANY_REQUESTS => Self::TooManyRequests,
+            UNAVAILABLE => Self::Unavailable,
+            "jwks_retrieval_failure" => Self::JwksRetrievalFailure,
+            "malformed_token" => Self::MalformedToken,
+            "invalid_headers" => Self::InvalidHeaders,
+            "invalid_token" => Self::InvalidToken,
+            "forbidden" => Self::Forbidden,
+            "invalid_algorithm" => Self::InvalidAlgorithm,
+            "discovery_failure" => Self::DiscoveryFailure,
+            "introspection_failure" => Self::IntrospectionFailure,
+            "revoked_token" => Self::RevokedToken,
+            "missing_credentials" => Self::MissingCredentials,
+            "malformed_credentials" => Self::MalformedCredentials,
+            "multiple_credentials" => Self::MultipleCredentials,
+            "unsupported_authorization_scheme" => Self::UnsupportedAuthorizationScheme,
+            "invalid_dpop_proof" => Self::InvalidDpopProof,
+            "invalid_key" => Self::InvalidKey,
+            "signing_failure" => Self::SigningFailure,
+            "missing_authentication_layer" => Self::MissingAuthenticationLayer,
+            other => Self::Other(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for ErrorKind {
+    fn from(value: String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl From<&String> for ErrorKind {
+    fn from(value: &String) -> Self {
+        Self::from(value.as_str())
+    }
+}
+
+impl From<std::io::ErrorKind> for ErrorKind {
+    fn from(value: std::io::ErrorKind) -> Self {
+        match value {
+            std::io::ErrorKind::NotFound => Self::NotFound,
+            std::io::ErrorKind::TimedOut => Self::TimedOut,
+            std::io::ErrorKind::Unsupported => Self::NotImplemented,
+            std::io::ErrorKind::InvalidData => Self::SerializationFailure,
+            _ => Self::IoFailure,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use log::Level;
+
+    use crate::error_kind::{
+        ErrorKind, ErrorKindMetadata, Fault, NOT_FOUND, SERIALIZATION_FAILURE, TOO_MANY_REQUESTS,
+        UNAVAILABLE,
+    };
+
+    #[test]
+    pub fn known_error_kinds_round_trip() {
+        for error_kind in [
+            NOT_FOUND,
+            SERIALIZATION_FAILURE,
+            TOO_MANY_REQUESTS,
+            UNAVAILABLE,
+            "invalid_token",
+        ] {
+            let typed_error_kind = ErrorKind::from(error_kind);
+
+            assert!(!matches!(typed_error_kind, ErrorKind::Other(_)));
+            assert_eq!(error_kind, typed_error_kind.as_str());
+        }
+    }
+
+    #[cfg(feature = "auth")]
+    #[test]
+    pub fn auth_error_kinds_are_typed() {
+        use crate::auth::error_kind::*;
+
+        for error_kind in [
+            JWKS_RETRIEVAL_FAILURE,
+            MALFORMED_TOKEN,
+            INVALID_HEADERS,
+            INVALID_TOKEN,
+            FORBIDDEN,
+            INVALID_ALGORITHM,
+            DISCOVERY_FAILURE,
+            INTROSPECTION_FAILURE,
+            REVOKED_TOKEN,
+            MISSING_CREDENTIALS,
+            MALFORMED_CREDENTIALS,
+            MULTIPLE_CREDENTIALS,
+            UNSUPPORTED_AUTHORIZATION_SCHEME,
+            INVALID_DPOP_PROOF,
+            INVALID_KEY,
+            SIGNING_FAILURE,
+            MISSING_AUTHENTICATION_LAYER,
+        ] {
+            let typed_error_kind = ErrorKind::from(error_kind);
+
+            assert!(!matches!(typed_error_kind, ErrorKind::Other(_)));
+            assert_eq!(error_kind, typed_error_kind.as_str());
+        }
+    }
+
+    #[test]
+    pub fn unknown_error_kind_is_kept_as_other() {
+        let error_kind = ErrorKind::from("quota_exceeded");
+
+        assert_eq!(ErrorKind::Other("quota_exceeded".to_string()), error_kind);
+        assert_eq!("quota_exceeded", error_kind.to_string());
+    }
+
+    #[test]
+    pub fn io_error_kinds_map_to_constants() {
+        assert_eq!(
+            ErrorKind::NotFound,
+            ErrorKind::from(std::io::ErrorKind::NotFound)
+        );
+        assert_eq!(
+            ErrorKind::IoFailure,
+            ErrorKind::from(std::io::ErrorKind::PermissionDenied)
+        );
+    }
+
+    #[test]
+    pub fn metadata_tells_transient_errors_apart() {
+        let too_many_requests = ErrorKind::TooManyRequests.metadata();
+        let not_found = ErrorKind::NotFound.metadata();
+        let serialization_failure = ErrorKind::SerializationFailure.metadata();
+
+        assert!(too_many_requests.is_retryable());
+        assert!(too_many_requests.is_transient());
+        assert!(!not_found.is_retryable());
+        assert_eq!(Fault::Client, not_found.fault());
+        assert_eq!(Fault::Server, serialization_failure.fault());
+        assert_eq!(Level::Error, serialization_failure.log_level());
+    }
+
+    #[test]
+    pub fn register_sets_metadata_of_unknown_kind() {
+        let metadata = ErrorKindMetadata::new(true, true, Fault::Client, Level::Warn);
+
+        ErrorKindMetadata::register("quota_exceeded_for_test", metadata);
+
+        assert_eq!(
+            metadata,
+            ErrorKind::from("quota_exceeded_for_test").metadata()
+        );
+        assert!(!ErrorKind::from("unregistered_for_test")
+            .metadata()
+            .is_retryable());
+    }
+
+    #[test]
+    pub fn from_status_code_maps_failed_responses() {
+        assert_eq!(ErrorKind::TooManyRequests, ErrorKind::from_status_code(429));
+        assert_eq!(ErrorKind::Unavailable, ErrorKind::from_status_code(503));
+        assert_eq!(ErrorKind::RequestFailure, ErrorKind::from_status_code(400));
+
+        for status_code in [401, 403, 404] {
+            let error_kind = ErrorKind::from_status_code(status_code);
+
+            assert_eq!(ErrorKind::RequestFailure, error_kind);
+            assert_eq!(Fault::Server, error_kind.metadata().fault());
+        }
+    }
+}
@@ -0,0 +1,424 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+use axum::extract::Request;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::error::Error;
+use crate::error_kind::{ErrorKind, Fault};
+
+pub const PROBLEM_JSON_CONTENT_TYPE: &str = "application/problem+json";
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-Id";
+
+// the messages of server errors may reveal internal details, so they are only logged
+const SERVER_ERROR_DETAIL: &str = "the request could not be processed, see the logs";
+const MAX_CORRELATION_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+static STATUS_CODE_TABLE: OnceLock<RwLock<StatusCodeTable>> = OnceLock::new();
+
+/// `StatusCodeTable` maps the error kinds to the HTTP status codes of their responses.
+///
+/// The kinds missing from the table are mapped by their fault, as per `ErrorKind::metadata`:
+/// client errors to `400 Bad Request` and server errors to the default status code. Services
+/// may replace the table through `install`, i.e. for mapping their own kinds.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusCodeTable {
+    status_codes: HashMap<ErrorKind, StatusCode>,
+    default_status_code: StatusCode,
+}
+
+impl Default for StatusCodeTable {
+    fn default() -> Self {
+        let status_codes = [
+            (ErrorKind::NotFound, StatusCode::NOT_FOUND),
+            (ErrorKind::NotImplemented, StatusCode::NOT_IMPLEMENTED),
+            (ErrorKind::TimedOut, StatusCode::GATEWAY_TIMEOUT),
+            (ErrorKind::RequestFailure, StatusCode::BAD_GATEWAY),
+            (ErrorKind::TooManyRequests, StatusCode::TOO_MANY_REQUESTS),
+            (ErrorKind::Unavailable, StatusCode::SERVICE_UNAVAILABLE),
+            (ErrorKind::MissingCredentials, StatusCode::UNAUTHORIZED),
+            (ErrorKind::InvalidToken, StatusCode::UNAUTHORIZED),
+            (ErrorKind::InvalidDpopProof, StatusCode::UNAUTHORIZED),
+            (ErrorKind::RevokedToken, StatusCode::UNAUTHORIZED),
+            (ErrorKind::Forbidden, StatusCode::FORBIDDEN),
+            (ErrorKind::MalformedToken, StatusCode::BAD_REQUEST),
+            (ErrorKind::InvalidHeaders, StatusCode::BAD_REQUEST),
+            (ErrorKind::InvalidAlgorithm, StatusCode::BAD_REQUEST),
+            (ErrorKind::MalformedCredentials, StatusCode::BAD_REQUEST),
+            (ErrorKind::MultipleCredentials, StatusCode::BAD_REQUEST),
+            (
+                ErrorKind::UnsupportedAuthorizationScheme,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                ErrorKind::JwksRetrievalFailure,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+            (ErrorKind::DiscoveryFailure, StatusCode::SERVICE_UNAVAILABLE),
+            (
+                ErrorKind::IntrospectionFailure,
+                StatusCode::SERVICE_UNAVAILABLE,
+            ),
+        ];
+
+        Self {
+            status_codes: status_codes.into_iter().collect(),
+            default_status_code: StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl StatusCodeTable {
+    ///
+    /// # Arguments
+    ///
+    /// * `error_kind` - Kind of the error, either an `ErrorKind` or an error kind constant.
+    /// * `status_code` - Status code of the responses for `error_kind`.
+    pub fn with_status_code(
+        mut self,
+        error_kind: impl Into<ErrorKind>,
+        status_code: StatusCode,
+    ) -> Self {
+        self.status_codes.insert(error_kind.into(), status_code);
+        self
+    }
+
+    /// Status code of the server errors missing from the table, `500 Internal Server Error` by
+    /// default.
+    pub fn with_default_status_code(mut self, status_code: StatusCode) -> Self {
+        self.default_status_code = status_code;
+        self
+    }
+
+    pub fn status_code(&self, error_kind: &ErrorKind) -> StatusCode {
+        match self.status_codes.get(error_kind) {
+            Some(status_code) => *status_code,
+            None => match error_kind.metadata().fault() {
+                Fault::Client => StatusCode::BAD_REQUEST,
+                Fault::Server => self.default_status_code,
+            },
+        }
+    }
+
+    /// Makes this table the one used by `status_code` and by the `IntoResponse` of `Error`.
+    pub fn install(self) {
+        if let Ok(mut table) = status_code_table().write() {
+            *table = self;
+        }
+    }
+}
+
+/// Maps the error to the status code of its response, as per the installed `StatusCodeTable`.
+pub fn status_code(error: &Error) -> StatusCode {
+    match status_code_table().read() {
+        Ok(table) => table.status_code(error.kind()),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+fn status_code_table() -> &'static RwLock<StatusCodeTable> {
+    STATUS_CODE_TABLE.get_or_init(|| RwLock::new(StatusCodeTable::default()))
+}
+
+/// `ProblemDetails` is the RFC 7807 body of the error responses.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: String,
+    status: u16,
+    detail: String,
+    kind: String,
+    correlation_id: String,
+}
+
+impl ProblemDetails {
+    /// The `detail` of server errors is generic, as their message is only meant for the logs.
+    pub fn new(error: &Error, status_code: StatusCode, correlation_id: impl Into<String>) -> Self {
+        let detail = if status_code.is_server_error() {
+            SERVER_ERROR_DETAIL.to_string()
+        } else {
+            error.message().to_string()
+        };
+
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: status_code
+                .canonical_reason()
+                .unwrap_or_default()
+                .to_string(),
+            status: status_code.as_u16(),
+            detail,
+            kind: error.error_kind().to_string(),
+            correlation_id: correlation_id.into(),
+        }
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn kind(&self) -> &str {
+        self.kind.as_str()
+    }
+
+    pub fn correlation_id(&self) -> &str {
+        self.correlation_id.as_str()
+    }
+}
+
+///
+/// Builds the `application/problem+json` response of the error. Server errors are also logged
+/// along with the correlation id, so they can be found from the response.
+///
+/// # Arguments
+///
+/// * `error` - Error to be returned to the client.
+/// * `correlation_id` - ID correlating the response with the logs, i.e. the request ID.
+pub fn problem_response(error: &Error, correlation_id: &str) -> Response {
+    let status_code = status_code(error);
+
+    if status_code.is_server_error() {
+        log::log!(
+            error.log_level(),
+            "request failed [{}]: {}",
+            correlation_id,
+            error
+        );
+    }
+
+    let body = match serde_json::to_string(&ProblemDetails::new(error, status_code, correlation_id))
+    {
+        Ok(body) => body,
+        Err(_) => return status_code.into_response(),
+    };
+    let mut response = (status_code, body).into_response();
+    let headers = response.headers_mut();
+    headers.insert(
+        CONTENT_TYPE,
+        HeaderValue::from_static(PROBLEM_JSON_CONTENT_TYPE),
+    );
+
+    if let Ok(correlation_id) = HeaderValue::from_str(correlation_id) {
+        headers.insert(CORRELATION_ID_HEADER, correlation_id);
+    }
+
+    response
+}
+
+///
+/// Gets the correlation id sent by the client through the `X-Correlation-Id` header, if it is
+/// valid, or generates a new one.
+///
+/// # Arguments
+///
+/// * `headers` - Headers of the request.
+pub fn correlation_id(headers: &HeaderMap) -> String {
+    headers
+        .get(CORRELATION_ID_HEADER)
+        .and_then(|correlation_id| correlation_id.to_str().ok())
+        .filter(|correlation_id| {
+            !correlation_id.is_empty()
+                && correlation_id.len() <= MAX_CORRELATION_ID_LENGTH
+                && correlation_id.chars().all(|c| c.is_ascii_graphic())
+        })
+        .map(String::from)
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+///
+/// Middleware making the correlation id of the request, as per `correlation_id`, the one used
+/// by the `IntoResponse` of `Error` while handling it. The id is returned within the
+/// `X-Correlation-Id` header of every response. Installed with `axum::middleware::from_fn`.
+///
+/// # Arguments
+///
+/// * `request` - Request to be handled.
+/// * `next` - Rest of the middleware stack.
+pub async fn correlation_id_middleware(request: Request, next: Next) -> Response {
+    let correlation_id = correlation_id(request.headers());
+    let mut response = CORRELATION_ID
+        .scope(correlation_id.clone(), next.run(request))
+        .await;
+
+    if let Ok(correlation_id) = HeaderValue::from_str(correlation_id.as_str()) {
+        response
+            .headers_mut()
+            .entry(CORRELATION_ID_HEADER)
+            .or_insert(correlation_id);
+    }
+
+    response
+}
+
+// errors outside of 'correlation_id_middleware' get a new correlation id
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let correlation_id = CORRELATION_ID
+            .try_with(Clone::clone)
+            .unwrap_or_else(|_| Uuid::new_v4().to_string());
+
+        problem_response(&self, correlation_id.as_str())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use axum::body::{to_bytes, Body};
+    use axum::extract::Request;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::http::{HeaderMap, HeaderValue, StatusCode};
+    use axum::middleware::from_fn;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::Router;
+    use serde_json::Value;
+    use tower::ServiceExt;
+
+    use crate::error::Error;
+    use crate::error_kind::{ErrorKind, NOT_FOUND};
+    use crate::error_response::{
+        correlation_id, correlation_id_middleware, problem_response, StatusCodeTable,
+        CORRELATION_ID_HEADER, PROBLEM_JSON_CONTENT_TYPE,
+    };
+
+    #[tokio::test]
+    pub async fn into_response_returns_problem_details() {
+        let response = Error::new(NOT_FOUND, "config 'Example' does not exist").into_response();
+
+        assert_eq!(StatusCode::NOT_FOUND, response.status());
+        assert_eq!(
+            Some(PROBLEM_JSON_CONTENT_TYPE),
+            response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+        );
+        let correlation_id = response
+            .headers()
+            .get(CORRELATION_ID_HEADER)
+            .expect("expected correlation id")
+            .to_str()
+            .expect("expected correlation id as string")
+            .to_string();
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("expected body");
+        let problem: Value = serde_json::from_slice(&body).expect("expected JSON body");
+        assert_eq!(404, problem["status"]);
+        assert_eq!("Not Found", problem["title"]);
+        assert_eq!(NOT_FOUND, problem["kind"]);
+        assert_eq!("config 'Example' does not exist", problem["detail"]);
+        assert_eq!(correlation_id, problem["correlation_id"]);
+    }
+
+    #[test]
+    pub fn problem_response_uses_given_correlation_id() {
+        let response = problem_response(&Error::new("quota_exceeded", "too many configs"), "req-1");
+
+        assert_eq!(StatusCode::INTERNAL_SERVER_ERROR, response.status());
+        assert_eq!(
+            Some("req-1"),
+            response
+                .headers()
+                .get(CORRELATION_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+        );
+    }
+
+    #[tokio::test]
+    pub async fn problem_response_hides_message_of_server_errors() {
+        let response = problem_response(
+            &Error::new(
+                "database_failure",
+                "password authentication failed for 'admin'",
+            ),
+            "req-1",
+        );
+
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("expected body");
+        let problem: Value = serde_json::from_slice(&body).expect("expected JSON body");
+        assert_eq!(500, problem["status"]);
+        assert_eq!("database_failure", problem["kind"]);
+        assert!(!problem["detail"]
+            .as_str()
+            .expect("expected detail")
+            .contains("password"));
+    }
+
+    #[tokio::test]
+    pub async fn correlation_id_middleware_uses_correlation_id_of_request() {
+        let router = Router::new()
+            .route(
+                "/",
+                get(|| async { Err::<(), Error>(Error::new(NOT_FOUND, "missing")) }),
+            )
+            .layer(from_fn(correlation_id_middleware));
+        let request = Request::builder()
+            .uri("/")
+            .header(CORRELATION_ID_HEADER, "req-42")
+            .body(Body::empty())
+            .expect("expected request");
+
+        let response = router.oneshot(request).await.expect("expected response");
+
+        assert_eq!(
+            Some("req-42"),
+            response
+                .headers()
+                .get(CORRELATION_ID_HEADER)
+                .and_then(|value| value.to_str().ok())
+        );
+        let body = to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("expected body");
+        let problem: Value = serde_json::from_slice(&body).expect("expected JSON body");
+        assert_eq!("req-42", problem["correlation_id"]);
+    }
+
+    #[test]
+    pub fn correlation_id_ignores_invalid_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CORRELATION_ID_HEADER, HeaderValue::from_static("a b"));
+
+        let correlation_id = correlation_id(&headers);
+
+        assert_ne!("a b", correlation_id);
+        assert!(!correlation_id.is_empty());
+    }
+
+    #[test]
+    pub fn status_code_table_can_be_customized() {
+        let table = StatusCodeTable::default()
+            .with_status_code("quota_exceeded", StatusCode::TOO_MANY_REQUESTS)
+            .with_default_status_code(StatusCode::BAD_GATEWAY);
+
+        assert_eq!(
+            StatusCode::TOO_MANY_REQUESTS,
+            table.status_code(&ErrorKind::from("quota_exceeded"))
+        );
+        assert_eq!(
+            StatusCode::BAD_GATEWAY,
+            table.status_code(&ErrorKind::from("unknown"))
+        );
+        assert_eq!(
+            StatusCode::UNAUTHORIZED,
+            table.status_code(&ErrorKind::InvalidToken)
+        );
+    }
+}
@@ -0,0 +1,504 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde_yaml::{Mapping, Value};
+
+use crate::config::file_getter::FileGetter;
+use crate::config_reader::ConfigReader;
+use crate::error::Error;
+
+const KEY_SPLIT: &str = ":";
+const ENVIRONMENT_VARIABLE_SPLIT: &str = "__";
+
+/// `LayeredConfigReader` reads the configuration from several layers, deep-merging each one
+/// over the previous ones. From lowest to highest precedence:
+///
+/// 1. The base file, i.e. `application.yaml`.
+/// 2. The environment overlay next to it, i.e. `application.production.yaml`, if it exists.
+/// 3. The environment variables starting with the prefix followed by `__`, where `__` nests the
+///    keys, i.e. `APP__Root__Parent__Child`.
+/// 4. The overrides, with keys such as `Root:Parent:Child`.
+///
+/// Mappings are merged key by key, while any other value replaces the previous one. The keys of
+/// the environment variables match the existing keys regardless of their case, the variables
+/// being applied in the order of their names so the last one wins when several match the same
+/// key, i.e. `APP__Database__Port` over `APP__DATABASE__PORT`. Their values
+/// are kept as strings, which `LayeredConfig::get` parses when another type is requested.
+pub struct LayeredConfigReader {
+    base_path: PathBuf,
+    environment: Option<String>,
+    environment_variable_prefix: Option<String>,
+    overrides: Vec<(String, Value)>,
+    config_reader: ConfigReader,
+}
+
+impl LayeredConfigReader {
+    ///
+    /// # Arguments
+    ///
+    /// * `base_path` - Path of the base configuration file, which must exist.
+    pub fn new(base_path: PathBuf) -> Self {
+        Self {
+            base_path,
+            environment: None,
+            environment_variable_prefix: None,
+            overrides: Vec::new(),
+            config_reader: ConfigReader::default(),
+        }
+    }
+
+    /// Merges the overlay of the environment, i.e. `production` for `application.production.yaml`.
+    pub fn with_environment(mut self, environment: impl Into<String>) -> Self {
+        self.environment = Some(environment.into());
+        self
+    }
+
+    /// Merges the environment variables starting with `prefix` followed by `__`.
+    pub fn with_environment_variable_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.environment_variable_prefix = Some(prefix.into());
+        self
+    }
+
+    ///
+    /// Sets a value over every other layer. Later overrides of the same key win.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - index that supports nesting by using ':', i.e. `Root:Parent:Child`.
+    /// * `value` - the value to be set.
+    pub fn with_override(mut self, key: impl Into<String>, value: impl Into<Value>) -> Self {
+        self.overrides.push((key.into(), value.into()));
+        self
+    }
+
+    ///
+    /// Reads and merges every layer.
+    ///
+    /// # Returns
+    ///
+    /// * __Ok__(`LayeredConfig`) - the merged configuration.
+    /// * __Err__(`Error`) - error indicating what went wrong, i.e. the base file does not exist.
+    pub fn read(&self) -> Result<LayeredConfig, Error> {
+        let mut value = self.config_reader.read(self.base_path.clone())?;
+
+        if let Some(overlay_path) = self.overlay_path() {
+            if overlay_path.exists() {
+                merge(&mut value, self.config_reader.read(overlay_path)?);
+            }
+        }
+
+        if let Some(ref prefix) = self.environment_variable_prefix {
+            let prefix = format!("{}{}", prefix, ENVIRONMENT_VARIABLE_SPLIT);
+
+            // the order of 'std::env::vars_os' is undefined, and unlike 'std::env::vars' it does
+            // not panic on the variables which are not valid unicode, which are skipped
+            let mut variables: Vec<(String, String)> = std::env::vars_os()
+                .filter_map(|(name, value)| {
+                    Some((name.into_string().ok()?, value.into_string().ok()?))
+                })
+                .filter(|(name, _)| name.starts_with(prefix.as_str()))
+                .collect();
+            variables.sort();
+
+            for (name, variable_value) in variables {
+                let keys: Vec<&str> = name[prefix.len()..]
+                    .split(ENVIRONMENT_VARIABLE_SPLIT)
+                    .filter(|key| !key.is_empty())
+                    .collect();
+
+                // without any key, the variable would replace the whole configuration
+                if keys.is_empty() {
+                    continue;
+                }
+
+                set(
+                    &mut value,
+                    keys.as_slice(),
+                    Value::String(variable_value),
+                    true,
+                );
+            }
+        }
+
+        for (key, override_value) in &self.overrides {
+            let keys: Vec<&str> = key.split(KEY_SPLIT).collect();
+            set(&mut value, keys.as_slice(), override_value.clone(), false);
+        }
+
+        Ok(LayeredConfig { value })
+    }
+
+    fn overlay_path(&self) -> Option<PathBuf> {
+        let environment = self.environment.as_ref()?;
+        let stem = self.base_path.file_stem()?.to_str()?;
+        let file_name = match self.base_path.extension().and_then(|ext| ext.to_str()) {
+            Some(extension) => format!("{}.{}.{}", stem, environment, extension),
+            None => format!("{}.{}", stem, environment),
+        };
+
+        Some(self.base_path.with_file_name(file_name))
+    }
+}
+
+/// `LayeredConfig` is the configuration merged by `LayeredConfigReader`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayeredConfig {
+    value: Value,
+}
+
+impl LayeredConfig {
+    /// `get` provides the configuration value for the specified key. String values, such as the
+    /// ones of environment variables, are parsed as YAML if they are not of type `T`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - index that supports nesting by using ':', i.e. `Root:Parent:Child:ExampleString`.
+    ///
+    /// # Returns
+    ///
+    /// * __Ok__(`T`) - the configuration value with the specified type.
+    /// * __Err__(`Error`) - error indicating what went wrong.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<T, Error> {
+        let keys: Vec<&str> = key.split(KEY_SPLIT).collect();
+        let value = FileGetter::inner_value(&self.value, keys.as_slice(), 0usize)?;
+
+        match serde_yaml::from_value::<T>(value.clone()) {
+            Ok(value) => Ok(value),
+            Err(error) => match value.as_str() {
+                Some(string_value) => {
+                    serde_yaml::from_str::<T>(string_value).map_err(|_| error.into())
+                }
+                None => Err(error.into()),
+            },
+        }
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+}
+
+// merges 'layer' into 'value', replacing everything but the mappings
+fn merge(value: &mut Value, layer: Value) {
+    match (value, layer) {
+        (Value::Mapping(mapping), Value::Mapping(layer_mapping)) => {
+            for (key, layer_value) in layer_mapping {
+                match mapping.get_mut(&key) {
+                    Some(value) => merge(value, layer_value),
+                    None => {
+                        mapping.insert(key, layer_value);
+                    }
+                }
+            }
+        }
+        (value, layer) => *value = layer,
+    }
+}
+
+fn set(value: &mut Value, keys: &[&str], new_value: Value, ignore_case: bool) {
+    let (current_key, inner_keys) = match keys.split_first() {
+        Some(split) => split,
+        None => {
+            merge(value, new_value);
+            return;
+        }
+    };
+
+    if !value.is_mapping() {
+        *value = Value::Mapping(Mapping::new());
+    }
+
+    let mapping = match value.as_mapping_mut() {
+        Some(mapping) => mapping,
+        None => return,
+    };
+    let existing_key = if ignore_case {
+        existing_key(mapping, current_key)
+    } else {
+        None
+    };
+    let key = existing_key.unwrap_or_else(|| Value::String(current_key.to_string()));
+
+    if !mapping.contains_key(&key) {
+        mapping.insert(key.clone(), Value::Null);
+    }
+
+    if let Some(inner_value) = mapping.get_mut(&key) {
+        set(inner_value, inner_keys, new_value, ignore_case);
+    }
+}
+
+fn existing_key(mapping: &Mapping, key: &str) -> Option<Value> {
+    mapping
+        .keys()
+        .find(|existing_key| {
+            existing_key
+                .as_str()
+                .is_some_and(|existing_key| existing_key.eq_ignore_ascii_case(key))
+        })
+        .cloned()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::path::PathBuf;
+
+    use crate::error_kind::NOT_FOUND;
+    use crate::layered_config::LayeredConfigReader;
+    use crate::test_base::get_unit_test_data_path;
+
+    #[test]
+    pub fn read_base_file_returns_its_values() {
+        let config = LayeredConfigReader::new(get_base_path())
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "localhost",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+        assert_eq!(
+            5432,
+            config.get::<i64>("Database:Port").expect("expected port")
+        );
+    }
+
+    #[test]
+    pub fn read_environment_overlay_merges_nested_values() {
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment("production")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "db.production",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+        assert_eq!(
+            5432,
+            config.get::<i64>("Database:Port").expect("expected port")
+        );
+        assert_eq!(
+            vec!["https://production".to_string()],
+            config
+                .get::<Vec<String>>("Cors:Origins")
+                .expect("expected origins")
+        );
+    }
+
+    #[test]
+    pub fn read_missing_environment_overlay_is_skipped() {
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment("staging")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "localhost",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+    }
+
+    #[test]
+    pub fn read_environment_variables_override_files() {
+        std::env::set_var("LAYERED_CONFIG_TEST__DATABASE__PORT", "6543");
+        std::env::set_var("LAYERED_CONFIG_TEST__Database__Pool__Size", "10");
+        std::env::set_var("LAYERED_CONFIG_TEST__Database__User", "admin");
+
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment("production")
+            .with_environment_variable_prefix("LAYERED_CONFIG_TEST")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            6543,
+            config.get::<i64>("Database:Port").expect("expected port")
+        );
+        assert_eq!(
+            10,
+            config
+                .get::<i64>("Database:Pool:Size")
+                .expect("expected pool size")
+        );
+        assert_eq!(
+            "admin",
+            config
+                .get::<String>("Database:User")
+                .expect("expected user")
+        );
+        assert_eq!(
+            "db.production",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+    }
+
+    #[test]
+    pub fn read_environment_variables_keep_numeric_looking_strings() {
+        std::env::set_var("LAYERED_CONFIG_STRING_TEST__Database__Password", "123456");
+        std::env::set_var("LAYERED_CONFIG_STRING_TEST__Database__User", "null");
+
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment_variable_prefix("LAYERED_CONFIG_STRING_TEST")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "123456",
+            config
+                .get::<String>("Database:Password")
+                .expect("expected password")
+        );
+        assert_eq!(
+            123456,
+            config
+                .get::<i64>("Database:Password")
+                .expect("expected password as number")
+        );
+        assert_eq!(
+            "null",
+            config
+                .get::<String>("Database:User")
+                .expect("expected user")
+        );
+    }
+
+    #[test]
+    pub fn read_environment_variables_matching_same_key_apply_in_name_order() {
+        std::env::set_var("LAYERED_CONFIG_ORDER_TEST__Database__Host", "db.mixed");
+        std::env::set_var("LAYERED_CONFIG_ORDER_TEST__DATABASE__HOST", "db.upper");
+        std::env::set_var("LAYERED_CONFIG_ORDER_TEST__database__host", "db.lower");
+
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment_variable_prefix("LAYERED_CONFIG_ORDER_TEST")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "db.lower",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+    }
+
+    #[test]
+    pub fn read_environment_variables_skip_empty_keys() {
+        std::env::set_var("LAYERED_CONFIG_EMPTY_KEY_TEST__", "db.root");
+        std::env::set_var(
+            "LAYERED_CONFIG_EMPTY_KEY_TEST____Database____Host__",
+            "db.nested",
+        );
+
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment_variable_prefix("LAYERED_CONFIG_EMPTY_KEY_TEST")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "db.nested",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+        assert_eq!(
+            5432,
+            config.get::<i64>("Database:Port").expect("expected port")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    pub fn read_environment_variables_skip_non_unicode_variables() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        std::env::set_var(
+            "LAYERED_CONFIG_UNICODE_TEST__Database__Host",
+            OsStr::from_bytes(b"db.\xff"),
+        );
+        std::env::set_var(
+            OsStr::from_bytes(b"LAYERED_CONFIG_UNICODE_TEST__Database__\xff"),
+            "db.invalid",
+        );
+        std::env::set_var("LAYERED_CONFIG_UNICODE_TEST__Database__User", "admin");
+
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment_variable_prefix("LAYERED_CONFIG_UNICODE_TEST")
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "localhost",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+        assert_eq!(
+            "admin",
+            config
+                .get::<String>("Database:User")
+                .expect("expected user")
+        );
+    }
+
+    #[test]
+    pub fn read_overrides_take_precedence() {
+        std::env::set_var("LAYERED_CONFIG_OVERRIDE_TEST__Database__Host", "db.env");
+
+        let config = LayeredConfigReader::new(get_base_path())
+            .with_environment("production")
+            .with_environment_variable_prefix("LAYERED_CONFIG_OVERRIDE_TEST")
+            .with_override("Database:Host", "db.override")
+            .with_override("Feature:Enabled", true)
+            .read()
+            .expect("expected configuration");
+
+        assert_eq!(
+            "db.override",
+            config
+                .get::<String>("Database:Host")
+                .expect("expected host")
+        );
+        assert!(config
+            .get::<bool>("Feature:Enabled")
+            .expect("expected feature flag"));
+    }
+
+    #[test]
+    pub fn get_not_existing_key_returns_error() {
+        let config = LayeredConfigReader::new(get_base_path())
+            .read()
+            .expect("expected configuration");
+
+        let result = config.get::<String>("Database:Missing");
+
+        assert_eq!(NOT_FOUND, result.unwrap_err().error_kind());
+    }
+
+    #[test]
+    pub fn read_not_existing_base_file_returns_error() {
+        let result = LayeredConfigReader::new(PathBuf::from("not_existing.yaml")).read();
+
+        assert_eq!(NOT_FOUND, result.unwrap_err().error_kind());
+    }
+
+    fn get_base_path() -> PathBuf {
+        let mut base_path = get_unit_test_data_path(file!());
+        base_path.push("application.yaml");
+
+        base_path
+    }
+}
@@ -6,7 +6,11 @@ pub mod config;
 pub mod config_reader;
 pub mod error;
 pub mod error_kind;
+#[cfg(feature = "http")]
+pub mod error_response;
+pub mod layered_config;
 pub mod macros;
+pub mod retry;
 pub mod secrets;
 pub mod test_base;
 
@@ -1,28 +1,137 @@
 /*
  * Copyright (c) Gabriel Amihalachioaie, SimpleG 2023.
  */
-use crate::error::Error;
+
+/// Unwraps a `Result`, or returns an `Error` of the specified kind whose message is followed by
+/// the original error, i.e. `failed to decode token's header: InvalidToken`.
 #[macro_export]
 macro_rules! ok_or_return_error {
     ($expression: expr, $error_kind: expr, $error_message: expr) => {
         match $expression {
             Ok(value) => value,
             Err(error) => {
-                return Err(Error::new(
+                return Err($crate::error::Error::new(
                     $error_kind,
-                    format!("{}{}", $error_message, error),
+                    $crate::macros::with_cause(
+                        ::std::string::ToString::to_string(&$error_message).as_str(),
+                        &error,
+                    ),
                 ))
             }
         }
     };
 }
 
+/// Unwraps an `Option`, or returns an `Error` of the specified kind.
 #[macro_export]
 macro_rules! some_or_return_error {
     ($expression: expr, $error_kind: expr, $error_message: expr) => {
         match $expression {
             Some(value) => value,
-            None => return Err(Error::new($error_kind, $error_message)),
+            None => return Err($crate::error::Error::new($error_kind, $error_message)),
+        }
+    };
+}
+
+/// Returns an `Error` of the specified kind, with either a message or a format string and its
+/// arguments. A lone format string may capture its arguments inline, i.e. `"{name} is missing"`.
+#[macro_export]
+macro_rules! bail {
+    ($error_kind: expr, $format: literal $(,)?) => {
+        return Err($crate::error::Error::new($error_kind, ::std::format!($format)))
+    };
+    ($error_kind: expr, $error_message: expr $(,)?) => {
+        return Err($crate::error::Error::new($error_kind, $error_message))
+    };
+    ($error_kind: expr, $format: literal, $($argument: tt)+) => {
+        return Err($crate::error::Error::new(
+            $error_kind,
+            ::std::format!($format, $($argument)+),
+        ))
+    };
+}
+
+/// Returns an `Error` of the specified kind unless the condition holds.
+#[macro_export]
+macro_rules! ensure {
+    ($condition: expr, $error_kind: expr, $($error_message: tt)+) => {
+        if !$condition {
+            $crate::bail!($error_kind, $($error_message)+);
         }
     };
 }
+
+/// Joins the message and the error which caused it. Messages may already end with the
+/// separator, as the ones of older callers do.
+#[doc(hidden)]
+pub fn with_cause(message: &str, cause: &dyn std::fmt::Display) -> String {
+    format!("{}: {}", message.trim_end().trim_end_matches(':'), cause)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::error_kind::{NOT_FOUND, SERIALIZATION_FAILURE};
+
+    #[test]
+    pub fn ok_or_return_error_separates_message_and_cause() {
+        for message in ["failed to parse", "failed to parse: "] {
+            let error = parse(message, "abc").expect_err("expected parse failure");
+
+            assert_eq!(SERIALIZATION_FAILURE, error.error_kind());
+            assert_eq!(
+                "failed to parse: invalid digit found in string",
+                error.message()
+            );
+        }
+    }
+
+    #[test]
+    pub fn bail_and_ensure_return_error() {
+        let bailed = find(0).expect_err("expected missing value");
+        let ensured = find(11).expect_err("expected out of range value");
+
+        assert_eq!(NOT_FOUND, bailed.error_kind());
+        assert_eq!("value 0 does not exist", bailed.message());
+        assert_eq!("value must be at most 10", ensured.message());
+        assert_eq!(Ok(5), find(5));
+    }
+
+    #[test]
+    pub fn bail_and_ensure_capture_inline_arguments() {
+        let bailed = find_inline(0).expect_err("expected missing value");
+        let ensured = find_inline(11).expect_err("expected out of range value");
+
+        assert_eq!("value 0 does not exist", bailed.message());
+        assert_eq!("value 11 must be at most 10", ensured.message());
+        assert_eq!(Ok(5), find_inline(5));
+    }
+
+    // no 'Error' import: the macros must work without it
+    fn parse(message: &str, value: &str) -> Result<u32, crate::error::Error> {
+        Ok(ok_or_return_error!(
+            value.parse::<u32>(),
+            SERIALIZATION_FAILURE,
+            message
+        ))
+    }
+
+    fn find(value: u32) -> Result<u32, crate::error::Error> {
+        ensure!(value <= 10, NOT_FOUND, "value must be at most 10");
+
+        if value == 0 {
+            bail!(NOT_FOUND, "value {} does not exist", value);
+        }
+
+        Ok(value)
+    }
+
+    fn find_inline(value: u32) -> Result<u32, crate::error::Error> {
+        ensure!(value <= 10, NOT_FOUND, "value {value} must be at most 10");
+
+        if value == 0 {
+            bail!(NOT_FOUND, "value {value} does not exist");
+        }
+
+        Ok(value)
+    }
+}
@@ -0,0 +1,457 @@
+/*
+ * Copyright (c) Gabriel Amihalachioaie, SimpleG 2024.
+ */
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use rand::{thread_rng, Rng};
+
+use crate::error::Error;
+
+const DEFAULT_MAX_ATTEMPTS: usize = 5;
+const DEFAULT_INITIAL_DELAY: Duration = Duration::from_millis(100);
+const DEFAULT_MAX_DELAY: Duration = Duration::from_secs(10);
+const DEFAULT_MULTIPLIER: f64 = 2.0;
+// as per the decorrelated jitter of the AWS architecture blog
+const DECORRELATED_MULTIPLIER: f64 = 3.0;
+
+type RetryPredicate = Arc<dyn Fn(&Error) -> bool + Send + Sync>;
+type RetryHook = Arc<dyn Fn(&RetryAttempt<'_>) + Send + Sync>;
+
+/// `Jitter` randomizes the delays between attempts, so clients failing at the same time do not
+/// retry at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Jitter {
+    /// Exponential delays, without randomization.
+    None,
+    /// Random delays between zero and the exponential delay.
+    Full,
+    /// Random delays between the initial delay and three times the previous delay.
+    Decorrelated,
+}
+
+/// `RetryAttempt` describes a failed attempt which is about to be retried.
+#[derive(Debug)]
+pub struct RetryAttempt<'a> {
+    attempt: usize,
+    delay: Duration,
+    elapsed: Duration,
+    error: &'a Error,
+}
+
+impl RetryAttempt<'_> {
+    /// Number of the failed attempt, starting at 1.
+    pub fn attempt(&self) -> usize {
+        self.attempt
+    }
+
+    /// Time to wait before the next attempt.
+    pub fn delay(&self) -> Duration {
+        self.delay
+    }
+
+    /// Time elapsed since the first attempt.
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn error(&self) -> &Error {
+        self.error
+    }
+}
+
+/// `RetryPolicy` retries failed operations with exponential backoff.
+///
+/// By default, operations are attempted up to 5 times, only retrying the errors whose kind is
+/// retryable, i.e. `TIMED_OUT`, `UNAVAILABLE` or `TOO_MANY_REQUESTS`, with full jitter over
+/// delays starting at 100ms and doubling up to 10s.
+#[derive(Clone)]
+pub struct RetryPolicy {
+    max_attempts: usize,
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    jitter: Jitter,
+    max_elapsed_time: Option<Duration>,
+    retryable: RetryPredicate,
+    on_retry: Option<RetryHook>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: DEFAULT_MAX_ATTEMPTS,
+            initial_delay: DEFAULT_INITIAL_DELAY,
+            max_delay: DEFAULT_MAX_DELAY,
+            multiplier: DEFAULT_MULTIPLIER,
+            jitter: Jitter::Full,
+            max_elapsed_time: None,
+            retryable: Arc::new(Error::is_retryable),
+            on_retry: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Maximum number of attempts, including the first one. `1` disables the retries.
+    pub fn with_max_attempts(mut self, max_attempts: usize) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    pub fn with_initial_delay(mut self, initial_delay: Duration) -> Self {
+        self.initial_delay = initial_delay;
+        self
+    }
+
+    pub fn with_max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = max_delay;
+        self
+    }
+
+    /// Factor applied to the delay after every attempt. Factors below `1` are raised to `1`.
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    pub fn with_jitter(mut self, jitter: Jitter) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Stops retrying once the next attempt would start after `max_elapsed_time` since the
+    /// first one.
+    pub fn with_max_elapsed_time(mut self, max_elapsed_time: Duration) -> Self {
+        self.max_elapsed_time = Some(max_elapsed_time);
+        self
+    }
+
+    /// Replaces the check of which errors are retried, `Error::is_retryable` by default.
+    pub fn with_retryable(
+        mut self,
+        retryable: impl Fn(&Error) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retryable = Arc::new(retryable);
+        self
+    }
+
+    /// Calls `on_retry` before waiting for every retry, i.e. `log_retry`.
+    pub fn with_on_retry(
+        mut self,
+        on_retry: impl Fn(&RetryAttempt<'_>) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_retry = Some(Arc::new(on_retry));
+        self
+    }
+
+    ///
+    /// Runs the asynchronous operation until it succeeds or must not be retried anymore.
+    ///
+    /// # Arguments
+    ///
+    /// * `operation` - Creates the future of each attempt.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok` - Result of the first successful attempt.
+    /// * `Err` - Error of the last attempt.
+    pub async fn retry<T, F, Fut>(&self, mut operation: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, Error>>,
+    {
+        let start = Instant::now();
+        let mut delay = Duration::ZERO;
+        let mut attempt = 1;
+
+        loop {
+            let error = match operation().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            delay = match self.next_delay(&error, attempt, delay, start.elapsed()) {
+                Some(delay) => delay,
+                None => return Err(error),
+            };
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Same as `retry`, but for blocking operations. The thread sleeps between attempts.
+    pub fn retry_blocking<T, F>(&self, mut operation: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Result<T, Error>,
+    {
+        let start = Instant::now();
+        let mut delay = Duration::ZERO;
+        let mut attempt = 1;
+
+        loop {
+            let error = match operation() {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+
+            delay = match self.next_delay(&error, attempt, delay, start.elapsed()) {
+                Some(delay) => delay,
+                None => return Err(error),
+            };
+
+            std::thread::sleep(delay);
+            attempt += 1;
+        }
+    }
+
+    // gets the delay before retrying the failed attempt, if it should be retried
+    fn next_delay(
+        &self,
+        error: &Error,
+        attempt: usize,
+        previous_delay: Duration,
+        elapsed: Duration,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !(self.retryable)(error) {
+            return None;
+        }
+
+        let delay = self.delay(attempt, previous_delay);
+
+        if let Some(max_elapsed_time) = self.max_elapsed_time {
+            if elapsed.saturating_add(delay) > max_elapsed_time {
+                return None;
+            }
+        }
+
+        if let Some(ref on_retry) = self.on_retry {
+            on_retry(&RetryAttempt {
+                attempt,
+                delay,
+                elapsed,
+                error,
+            });
+        }
+
+        Some(delay)
+    }
+
+    fn delay(&self, attempt: usize, previous_delay: Duration) -> Duration {
+        let exponent = i32::try_from(attempt.saturating_sub(1)).unwrap_or(i32::MAX);
+        let exponential_delay = scaled_delay(
+            self.initial_delay,
+            self.multiplier.powi(exponent),
+            self.max_delay,
+        );
+
+        match self.jitter {
+            Jitter::None => exponential_delay,
+            Jitter::Full => random_delay(Duration::ZERO, exponential_delay),
+            Jitter::Decorrelated => {
+                let previous_delay = previous_delay.max(self.initial_delay);
+
+                random_delay(
+                    self.initial_delay,
+                    scaled_delay(previous_delay, DECORRELATED_MULTIPLIER, self.max_delay),
+                )
+                .min(self.max_delay)
+            }
+        }
+    }
+}
+
+/// Logs the retried attempt at the log level of its error.
+pub fn log_retry(attempt: &RetryAttempt<'_>) {
+    log::log!(
+        attempt.error().log_level(),
+        "attempt {} failed, retrying in {:?}: {}",
+        attempt.attempt(),
+        attempt.delay(),
+        attempt.error()
+    );
+}
+
+// multiplies in seconds, as 'Duration::mul_f64' panics when the delay overflows
+fn scaled_delay(delay: Duration, factor: f64, max_delay: Duration) -> Duration {
+    if delay.is_zero() {
+        return Duration::ZERO;
+    }
+
+    Duration::try_from_secs_f64(delay.as_secs_f64() * factor)
+        .unwrap_or(max_delay)
+        .min(max_delay)
+}
+
+fn random_delay(min: Duration, max: Duration) -> Duration {
+    if max <= min {
+        return min;
+    }
+
+    Duration::try_from_secs_f64(thread_rng().gen_range(min.as_secs_f64()..=max.as_secs_f64()))
+        .unwrap_or(max)
+        .min(max)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use crate::error::Error;
+    use crate::error_kind::{NOT_FOUND, TIMED_OUT};
+    use crate::retry::{Jitter, RetryPolicy};
+
+    #[tokio::test]
+    pub async fn retry_retries_retryable_errors_until_success() {
+        let attempts = AtomicUsize::new(0);
+        let retries = Arc::new(AtomicUsize::new(0));
+        let hook_retries = retries.clone();
+        let retry_policy = get_retry_policy().with_on_retry(move |attempt| {
+            hook_retries.store(attempt.attempt(), Ordering::SeqCst);
+        });
+
+        let result = retry_policy
+            .retry(|| async {
+                match attempts.fetch_add(1, Ordering::SeqCst) {
+                    0 | 1 => Err(Error::new(TIMED_OUT, "timed out")),
+                    _ => Ok("done"),
+                }
+            })
+            .await;
+
+        assert_eq!(Ok("done"), result);
+        assert_eq!(3, attempts.load(Ordering::SeqCst));
+        assert_eq!(2, retries.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    pub fn retry_blocking_does_not_retry_permanent_errors() {
+        let mut attempts = 0;
+
+        let result: Result<(), Error> = get_retry_policy().retry_blocking(|| {
+            attempts += 1;
+            Err(Error::new(NOT_FOUND, "missing"))
+        });
+
+        assert_eq!(NOT_FOUND, result.expect_err("expected error").error_kind());
+        assert_eq!(1, attempts);
+    }
+
+    #[test]
+    pub fn retry_blocking_stops_after_max_attempts() {
+        let mut attempts = 0;
+
+        let result: Result<(), Error> =
+            get_retry_policy().with_max_attempts(4).retry_blocking(|| {
+                attempts += 1;
+                Err(Error::new(TIMED_OUT, "timed out"))
+            });
+
+        assert!(result.is_err());
+        assert_eq!(4, attempts);
+    }
+
+    #[test]
+    pub fn retry_blocking_stops_after_max_elapsed_time() {
+        let mut attempts = 0;
+
+        let result: Result<(), Error> = RetryPolicy::default()
+            .with_jitter(Jitter::None)
+            .with_initial_delay(Duration::from_millis(20))
+            .with_max_elapsed_time(Duration::from_millis(50))
+            .retry_blocking(|| {
+                attempts += 1;
+                Err(Error::new(TIMED_OUT, "timed out"))
+            });
+
+        // waits 20ms, then 40ms would exceed the 50ms
+        assert!(result.is_err());
+        assert_eq!(2, attempts);
+    }
+
+    #[test]
+    pub fn retry_blocking_uses_custom_predicate() {
+        let mut attempts = 0;
+
+        let result: Result<(), Error> = get_retry_policy()
+            .with_retryable(|error| error.error_kind() == NOT_FOUND)
+            .retry_blocking(|| {
+                attempts += 1;
+                Err(Error::new(NOT_FOUND, "missing"))
+            });
+
+        assert!(result.is_err());
+        assert_eq!(5, attempts);
+    }
+
+    #[test]
+    pub fn delay_stays_within_jitter_bounds() {
+        let retry_policy = RetryPolicy::default()
+            .with_initial_delay(Duration::from_millis(100))
+            .with_max_delay(Duration::from_secs(1));
+        let exponential = retry_policy.clone().with_jitter(Jitter::None);
+        let full = retry_policy.clone().with_jitter(Jitter::Full);
+        let decorrelated = retry_policy.with_jitter(Jitter::Decorrelated);
+
+        assert_eq!(
+            Duration::from_millis(400),
+            exponential.delay(3, Duration::ZERO)
+        );
+        assert_eq!(
+            Duration::from_secs(1),
+            exponential.delay(10, Duration::ZERO)
+        );
+
+        for _ in 0..100 {
+            assert!(full.delay(3, Duration::ZERO) <= Duration::from_millis(400));
+
+            let delay = decorrelated.delay(2, Duration::from_millis(200));
+            assert!(delay >= Duration::from_millis(100));
+            assert!(delay <= Duration::from_millis(600));
+        }
+    }
+
+    #[test]
+    pub fn delay_does_not_overflow() {
+        let retry_policy = RetryPolicy::default()
+            .with_max_attempts(2000)
+            .with_max_delay(Duration::MAX)
+            .with_multiplier(f64::MAX);
+        let exponential = retry_policy.clone().with_jitter(Jitter::None);
+        let full = retry_policy.clone().with_jitter(Jitter::Full);
+        let decorrelated = retry_policy.with_jitter(Jitter::Decorrelated);
+
+        assert_eq!(Duration::MAX, exponential.delay(2000, Duration::ZERO));
+        assert!(full.delay(usize::MAX, Duration::ZERO) <= Duration::MAX);
+        assert!(decorrelated.delay(2000, Duration::MAX) >= Duration::from_millis(100));
+    }
+
+    #[test]
+    pub fn with_multiplier_raises_factors_below_one() {
+        let retry_policy = RetryPolicy::default()
+            .with_jitter(Jitter::None)
+            .with_multiplier(-1.0);
+
+        assert_eq!(
+            Duration::from_millis(100),
+            retry_policy.delay(3, Duration::ZERO)
+        );
+        assert_eq!(
+            Duration::from_millis(100),
+            retry_policy
+                .with_multiplier(f64::NAN)
+                .delay(3, Duration::ZERO)
+        );
+    }
+
+    fn get_retry_policy() -> RetryPolicy {
+        RetryPolicy::default()
+            .with_jitter(Jitter::None)
+            .with_initial_delay(Duration::ZERO)
+    }
+}
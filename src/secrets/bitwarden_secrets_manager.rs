@@ -5,11 +5,11 @@
 use std::process::Command;
 use std::time::Duration;
 
-use rand::{thread_rng, Rng};
 use serde::Deserialize;
 
 use crate::error::Error;
-use crate::error_kind::SECRETS_MANAGER_FAILURE;
+use crate::error_kind::{SECRETS_MANAGER_FAILURE, TOO_MANY_REQUESTS};
+use crate::retry::{log_retry, Jitter, RetryPolicy};
 use crate::secrets::secrets_manager::SecretsManager;
 
 const MAX_RETRIES: usize = 5;
@@ -30,18 +30,29 @@ struct BitwardenSecret {
 
 pub struct BitwardenSecretsManager {
     access_token: String,
+    retry_policy: RetryPolicy,
 }
 
 impl BitwardenSecretsManager {
     pub fn new(access_token: String) -> Self {
-        Self { access_token }
+        let retry_policy = RetryPolicy::default()
+            .with_max_attempts(MAX_RETRIES + 1)
+            .with_initial_delay(Duration::from_millis(
+                MIN_SLEEP_BETWEEN_TRIES_IN_MILLISECONDS,
+            ))
+            .with_max_delay(Duration::from_millis(
+                MAX_SLEEP_BETWEEN_TRIES_IN_MILLISECONDS,
+            ))
+            .with_jitter(Jitter::Decorrelated)
+            .with_on_retry(log_retry);
+
+        Self {
+            access_token,
+            retry_policy,
+        }
     }
 
-    fn get_secret_after_too_many_requests_failure(
-        &self,
-        secret_id: &str,
-        count: usize,
-    ) -> Result<String, Error> {
+    fn try_get_secret(&self, secret_id: &str) -> Result<String, Error> {
         let result = Command::new("bws")
             .arg("get")
             .arg("secret")
@@ -59,27 +70,7 @@ impl BitwardenSecretsManager {
                     }
                 } else {
                     return match String::from_utf8(output.stderr) {
-                        Ok(error_message) => {
-                            if error_message.contains("[429 Too Many Requests]")
-                                && count < MAX_RETRIES
-                            {
-                                let mut rng = thread_rng();
-                                let sleep_for_milliseconds = rng.gen_range(
-                                    MIN_SLEEP_BETWEEN_TRIES_IN_MILLISECONDS
-                                        ..MAX_SLEEP_BETWEEN_TRIES_IN_MILLISECONDS,
-                                );
-                                std::thread::sleep(Duration::from_millis(sleep_for_milliseconds));
-                                return self.get_secret_after_too_many_requests_failure(
-                                    secret_id,
-                                    count + 1,
-                                );
-                            }
-
-                            Err(Error::new(
-                                SECRETS_MANAGER_FAILURE.to_string(),
-                                format!("failed to run 'bws': {}", error_message),
-                            ))
-                        }
+                        Ok(error_message) => Err(bws_error(error_message)),
                         Err(error) => Err(Error::new(
                             SECRETS_MANAGER_FAILURE.to_string(),
                             format!("failed to read 'bws' error: {}", error),
@@ -96,14 +87,30 @@ impl BitwardenSecretsManager {
 
 impl SecretsManager for BitwardenSecretsManager {
     fn get_secret(&self, secret_id: &str) -> Result<String, Error> {
-        self.get_secret_after_too_many_requests_failure(secret_id, 0usize)
+        self.retry_policy
+            .retry_blocking(|| self.try_get_secret(secret_id))
     }
 }
 
+// 'bws' only reports the HTTP status of the failed request within its error message
+fn bws_error(error_message: String) -> Error {
+    let error_kind = if error_message.contains("[429 Too Many Requests]") {
+        TOO_MANY_REQUESTS
+    } else {
+        SECRETS_MANAGER_FAILURE
+    };
+
+    Error::new(
+        error_kind,
+        format!("failed to run 'bws': {}", error_message),
+    )
+}
+
 #[cfg(test)]
 pub mod tests {
     use crate::config_reader::ConfigReader;
-    use crate::secrets::bitwarden_secrets_manager::BitwardenSecretsManager;
+    use crate::error_kind::{SECRETS_MANAGER_FAILURE, TOO_MANY_REQUESTS};
+    use crate::secrets::bitwarden_secrets_manager::{bws_error, BitwardenSecretsManager};
     use crate::secrets::secrets_manager::SecretsManager;
     use crate::test_base::get_unit_test_data_path;
 
@@ -133,4 +140,15 @@ pub mod tests {
             get_secret_existing_secret_returns_expected_string();
         }
     }
+
+    #[test]
+    fn bws_error_with_too_many_requests_is_retryable() {
+        let error = bws_error("Error: [429 Too Many Requests] slow down".to_string());
+        let other_error = bws_error("Error: [404 Not Found]".to_string());
+
+        assert_eq!(TOO_MANY_REQUESTS, error.error_kind());
+        assert!(error.is_retryable());
+        assert_eq!(SECRETS_MANAGER_FAILURE, other_error.error_kind());
+        assert!(!other_error.is_retryable());
+    }
 }
@@ -21,3 +21,16 @@ pub fn get_unit_test_data_path(test_file_name: &str) -> PathBuf {
 
     test_data_path.to_path_buf()
 }
+
+/// Builds an unsigned `JwtToken` carrying the given claims, for testing what is done with a
+/// token once validated.
+#[cfg(all(test, feature = "auth"))]
+pub fn get_jwt_token(claims: serde_json::Value) -> crate::auth::jwt_token::JwtToken {
+    let claims: std::collections::HashMap<String, serde_json::Value> =
+        serde_json::from_value(claims).expect("expected claims map");
+
+    crate::auth::jwt_token::JwtToken::new(jsonwebtoken::TokenData {
+        header: jsonwebtoken::Header::default(),
+        claims,
+    })
+}